@@ -0,0 +1,37 @@
+// Maker-signed withdrawal authorizations for NEAR HTLC Escrow
+// Lets a relayer withdraw on behalf of a maker without NEAR for gas
+use near_sdk::{borsh, env, near, AccountId, CurveType, PublicKey};
+
+/// What the maker signs: the borsh serialization of this struct, signed with ed25519
+#[near(serializers = [json, borsh])]
+pub struct WithdrawalAuthorization {
+    pub escrow_id: AccountId, // Account of the escrow, so the signature can't be used elsewhere
+    pub recipient: AccountId, // Where the amount is sent
+    pub expires_at: u64,      // Timestamp in seconds after which the authorization is rejected
+    pub nonce: u64,           // Each nonce can only be used once per escrow
+}
+
+impl WithdrawalAuthorization {
+    /// Bytes the maker signs
+    pub fn message(&self) -> Vec<u8> {
+        borsh::to_vec(self).unwrap()
+    }
+
+    /// Verify a 64-byte hex ed25519 signature of the message against `public_key`
+    pub fn verify(&self, public_key: &PublicKey, signature: &str) -> bool {
+        if public_key.curve_type() != CurveType::ED25519 {
+            return false;
+        }
+        // PublicKey bytes start with the curve type
+        let Ok(public_key) = <[u8; 32]>::try_from(&public_key.as_bytes()[1..]) else {
+            return false;
+        };
+        let Some(signature) = hex::decode(signature.trim_start_matches("0x"))
+            .ok()
+            .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+        else {
+            return false;
+        };
+        env::ed25519_verify(&signature, &self.message(), &public_key)
+    }
+}
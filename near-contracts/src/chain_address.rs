@@ -0,0 +1,181 @@
+// Counterparty addresses on the chains X3Fusion swaps with
+use near_sdk::{env, near, require, AccountId};
+
+/// Tezos base58check prefixes of implicit (tz1 / tz2 / tz3) and originated (KT1) addresses
+const TEZOS_PREFIXES: [(&str, [u8; 3]); 4] = [
+    ("tz1", [6, 161, 159]),
+    ("tz2", [6, 161, 161]),
+    ("tz3", [6, 161, 164]),
+    ("KT1", [2, 90, 121]),
+];
+
+/// Address of the taker on the other leg of the swap
+/// JSON: `{"chain": "evm", "address": "0x..."}`, `{"chain": "tezos", "address": "tz1..."}`
+#[near(serializers = [json,borsh])]
+#[serde(tag = "chain", content = "address", rename_all = "snake_case")]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ChainAddress {
+    Evm(String),   // 20-byte hex, EIP-55 checksummed if mixed case
+    Tezos(String), // tz1 / tz2 / tz3 / KT1 base58check
+    Near(AccountId),
+}
+
+impl ChainAddress {
+    /// Panic if the address is not valid on its chain
+    pub fn validate(&self) {
+        match self {
+            ChainAddress::Evm(address) => Self::validate_evm(address),
+            ChainAddress::Tezos(address) => Self::validate_tezos(address),
+            // AccountId is validated when deserialized
+            ChainAddress::Near(_) => {}
+        }
+    }
+
+    /// Raw address bytes: 20 bytes for EVM, prefix + 20-byte hash for Tezos, account id for NEAR
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            ChainAddress::Evm(address) => hex::decode(address.trim_start_matches("0x")).unwrap(),
+            ChainAddress::Tezos(address) => {
+                let decoded = bs58::decode(address).into_vec().unwrap();
+                decoded[..decoded.len() - 4].to_vec()
+            }
+            ChainAddress::Near(account_id) => account_id.as_bytes().to_vec(),
+        }
+    }
+
+    /// Tag used in the canonical immutables hash
+    pub fn chain_id(&self) -> u8 {
+        match self {
+            ChainAddress::Evm(_) => 0,
+            ChainAddress::Tezos(_) => 1,
+            ChainAddress::Near(_) => 2,
+        }
+    }
+
+    /// EVM address (with 0x prefix), None for other chains
+    pub fn as_evm(&self) -> Option<String> {
+        match self {
+            ChainAddress::Evm(address) if address.starts_with("0x") => Some(address.clone()),
+            ChainAddress::Evm(address) => Some(format!("0x{}", address)),
+            _ => None,
+        }
+    }
+
+    /// Address as a string, as it is written on its chain
+    pub fn to_address_string(&self) -> String {
+        match self {
+            ChainAddress::Evm(_) => self.as_evm().unwrap(),
+            ChainAddress::Tezos(address) => address.clone(),
+            ChainAddress::Near(account_id) => account_id.to_string(),
+        }
+    }
+
+    /// Validate EVM address format (20-byte hex string) and its EIP-55 checksum
+    /// All lowercase or all uppercase addresses carry no checksum and are accepted as is
+    fn validate_evm(address: &str) {
+        let cleaned_address = address.strip_prefix("0x").unwrap_or(address);
+
+        require!(
+            cleaned_address.len() == 40,
+            "EVM address must be 20 bytes (40 hex characters)"
+        );
+
+        require!(
+            cleaned_address.chars().all(|c| c.is_ascii_hexdigit()),
+            "EVM address must contain only hex characters"
+        );
+
+        let has_lower = cleaned_address.chars().any(|c| c.is_ascii_lowercase());
+        let has_upper = cleaned_address.chars().any(|c| c.is_ascii_uppercase());
+        if !(has_lower && has_upper) {
+            return;
+        }
+
+        // A letter is uppercase iff the matching nibble of keccak256(lowercase address) is >= 8
+        let hash = env::keccak256(cleaned_address.to_ascii_lowercase().as_bytes());
+        let checksum_ok = cleaned_address.chars().enumerate().all(|(i, c)| {
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            !c.is_ascii_alphabetic() || c.is_ascii_uppercase() == (nibble >= 8)
+        });
+        require!(checksum_ok, "Invalid EIP-55 checksum in EVM address");
+    }
+
+    /// Validate a Tezos tz1 / tz2 / tz3 / KT1 address: known prefix, 20-byte hash
+    /// and the base58check checksum (first 4 bytes of sha256(sha256(payload)))
+    fn validate_tezos(address: &str) {
+        let prefix = TEZOS_PREFIXES
+            .iter()
+            .find(|(name, _)| address.starts_with(name))
+            .map(|(_, bytes)| bytes)
+            .unwrap_or_else(|| {
+                env::panic_str("Tezos address must start with tz1, tz2, tz3 or KT1")
+            });
+
+        let decoded = bs58::decode(address)
+            .into_vec()
+            .unwrap_or_else(|_| env::panic_str("Tezos address must be base58 encoded"));
+        require!(
+            decoded.len() == 3 + 20 + 4 && decoded.starts_with(prefix),
+            "Tezos address must hold a 20-byte hash"
+        );
+
+        let (payload, checksum) = decoded.split_at(decoded.len() - 4);
+        let hash = env::sha256(&env::sha256(payload));
+        require!(
+            &hash[..4] == checksum,
+            "Invalid base58check checksum in Tezos address"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evm(address: &str) -> ChainAddress {
+        ChainAddress::Evm(address.to_string())
+    }
+
+    fn tezos(address: &str) -> ChainAddress {
+        ChainAddress::Tezos(address.to_string())
+    }
+
+    #[test]
+    fn valid_evm_addresses() {
+        // EIP-55 example, all lowercase, all uppercase and without 0x prefix
+        evm("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").validate();
+        evm("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").validate();
+        evm("0x5AAEB6053F3E94C9B9A09F33669435E7EF1BEAED").validate();
+        evm("5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "EVM address must be 20 bytes (40 hex characters)")]
+    fn short_evm_address_is_invalid() {
+        evm("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeA").validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid EIP-55 checksum in EVM address")]
+    fn wrong_evm_checksum_is_invalid() {
+        evm("0x5aaeb6053F3E94C9b9A09f33669435E7Ef1BeAed").validate();
+    }
+
+    #[test]
+    fn valid_tezos_addresses() {
+        tezos("tz1a4XeitzFQL5kKXtEYdC7ptmPWJwDD12XN").validate();
+        tezos("KT1D9qehRdgVzEv1FgPNESB1DnxdekSdNbHN").validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "Tezos address must start with tz1, tz2, tz3 or KT1")]
+    fn unknown_tezos_prefix_is_invalid() {
+        tezos("tz4a4XeitzFQL5kKXtEYdC7ptmPWJwDD12XN").validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid base58check checksum in Tezos address")]
+    fn wrong_tezos_checksum_is_invalid() {
+        tezos("tz1a4XeitzFQL5kKXtEYdC7ptmPWJwDD12XM").validate();
+    }
+}
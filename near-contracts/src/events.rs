@@ -0,0 +1,85 @@
+// NEP-297 events for NEAR HTLC Escrow
+// Logged as `EVENT_JSON:{"standard":"htlc_escrow","version":"1.0.0","event":...,"data":...}`
+// so indexers can follow the escrow lifecycle without parsing log strings
+use near_sdk::json_types::U128;
+use near_sdk::{near, AccountId};
+
+use crate::{ChainAddress, PayoutKind};
+
+#[near(event_json(standard = "htlc_escrow"))]
+pub enum EscrowEvent<'a> {
+    /// Escrow deployed and the native deposit received
+    #[event_version("1.0.0")]
+    EscrowCreated {
+        order_hash: &'a str,
+        hashlock: &'a str,
+        maker: &'a AccountId,
+        token: Option<&'a AccountId>,
+        amount: U128,
+        safety_deposit: U128,
+        deposit: U128,
+    },
+    /// Token escrow funded through `ft_transfer_call`, `funder` gets the tokens back on cancellation
+    #[event_version("1.0.0")]
+    EscrowFunded {
+        order_hash: &'a str,
+        token: &'a AccountId,
+        amount: U128,
+        funder: &'a AccountId,
+    },
+    /// Secret revealed and funds released (`part_index` is set for Merkle partial fills)
+    /// Counterpart of `EscrowWithdrawal(bytes32 secret)` on EVM
+    #[event_version("1.0.0")]
+    EscrowWithdrawn {
+        order_hash: &'a str,
+        hashlock: &'a str,
+        secret: &'a str,
+        part_index: Option<u32>,
+        amount: U128,
+        recipient: &'a AccountId,
+        taker_address: &'a ChainAddress,
+    },
+    /// Locked funds returned after the cancellation timelock, the caller earned the safety deposit
+    #[event_version("1.0.0")]
+    EscrowCancelled {
+        order_hash: &'a str,
+        hashlock: &'a str,
+        amount: U128,
+        recipient: &'a AccountId,
+    },
+    /// Locked funds and the safety deposit returned to the refund recipient after the emergency delay
+    #[event_version("1.0.0")]
+    EmergencyRefunded {
+        order_hash: &'a str,
+        hashlock: &'a str,
+        amount: U128,
+        safety_deposit: U128,
+        recipient: &'a AccountId,
+    },
+    /// Depositor rescued stuck funds after the rescue delay (`token` is None for native NEAR)
+    #[event_version("1.0.0")]
+    FundsRescued {
+        order_hash: &'a str,
+        token: Option<&'a AccountId>,
+        amount: U128,
+        recipient: &'a AccountId,
+    },
+    /// A payout transfer failed and the funds stayed in the escrow
+    /// `kind` is None for rescues and for the NEAR side payouts (safety / excess deposit),
+    /// the latter can't be rolled back
+    #[event_version("1.0.0")]
+    PayoutFailed {
+        order_hash: &'a str,
+        receiver: &'a AccountId,
+        token: Option<&'a AccountId>,
+        amount: U128,
+        kind: Option<PayoutKind>,
+        rolled_back: bool,
+    },
+    /// Escrow account deleted, the remaining balance went to `beneficiary`
+    #[event_version("1.0.0")]
+    EscrowClosed {
+        order_hash: &'a str,
+        beneficiary: &'a AccountId,
+    },
+}
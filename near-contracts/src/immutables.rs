@@ -3,18 +3,46 @@ use near_sdk::borsh;
 use near_sdk::json_types::U128;
 use near_sdk::{
     env, near, require,
-    AccountId,
+    AccountId, CurveType, PublicKey,
 };
 
+use crate::chain_address::ChainAddress;
+use crate::merkle;
+
+/// Hash function applied to the secret (and the Merkle tree of secrets)
+/// Keccak256 matches the EVM escrows, so one secret unlocks both legs
+#[near(serializers = [json,borsh])]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Keccak256,
+}
+
+impl HashAlgorithm {
+    pub fn hash(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha256 => env::sha256(data),
+            HashAlgorithm::Keccak256 => env::keccak256(data),
+        }
+    }
+}
+
 /// Immutable escrow parameters - set once during creation
 #[near(serializers = [json,borsh])]
+#[derive(Clone)]
 pub struct EscrowImmutables {
-    pub order_hash: String,        // 32-byte hex string
-    pub hashlock: String,          // 32-byte hex string
-    pub maker: AccountId,          // NEAR account
-    pub taker_evm_address: String, // EVM address as hex string
-    pub amount: U128,              // NEAR amount in yoctoNEAR
-    pub safety_deposit: U128,      // Safety deposit amount
+    pub order_hash: String,                  // 32-byte hex string
+    pub hashlock: String,                    // 32-byte hex string (Merkle root for partial fills)
+    pub hash_algorithm: HashAlgorithm,       // How the secret is hashed into the hashlock
+    pub parts_count: Option<u32>,            // Number of Merkle secrets, None for a single secret
+    pub maker: AccountId,                    // NEAR account
+    pub maker_public_key: Option<PublicKey>, // ed25519 key that signs withdrawal authorizations
+    pub taker_address: ChainAddress,         // Taker on the other chain (EVM, Tezos or NEAR)
+    pub token: Option<AccountId>,            // NEP-141 token contract, None for native NEAR
+    pub amount: U128,                        // Amount in yoctoNEAR or token base units
+    pub safety_deposit: U128,                // Safety deposit amount
 }
 
 impl EscrowImmutables {
@@ -22,8 +50,12 @@ impl EscrowImmutables {
     pub fn new(
         order_hash: String,
         hashlock: String,
+        hash_algorithm: HashAlgorithm,
+        parts_count: Option<u32>,
         maker: AccountId,
-        taker_evm_address: String,
+        maker_public_key: Option<PublicKey>,
+        taker_address: ChainAddress,
+        token: Option<AccountId>,
         amount: U128,
         safety_deposit: U128,
     ) -> Self {
@@ -33,18 +65,36 @@ impl EscrowImmutables {
         // Validate hashlock format
         Self::validate_hashlock(&hashlock);
 
-        // Validate EVM address format
-        Self::validate_evm_address(&taker_evm_address);
+        // Validate taker address for its chain
+        taker_address.validate();
 
         
         require!(amount.0 > 0, "Amount must be greater than 0");
-        
+
+        if let Some(public_key) = &maker_public_key {
+            require!(
+                public_key.curve_type() == CurveType::ED25519,
+                "Maker public key must be an ed25519 key"
+            );
+        }
+
+        if let Some(parts) = parts_count {
+            require!(parts >= 2, "Partial fills need at least 2 secrets");
+            require!(
+                amount.0 >= parts as u128,
+                "Amount is too small to split into the requested parts"
+            );
+        }
 
         Self {
             order_hash,
             hashlock,
+            hash_algorithm,
+            parts_count,
             maker,
-            taker_evm_address,
+            maker_public_key,
+            taker_address,
+            token,
             amount,
             safety_deposit,
         }
@@ -92,42 +142,157 @@ impl EscrowImmutables {
         );
     }
 
-    /// Validate EVM address format (20-byte hex string)
-    fn validate_evm_address(address: &str) {
-        require!(!address.is_empty(), "EVM address cannot be empty");
+    /// Get total required deposit (amount + safety deposit)
+    pub fn get_total_required(&self) -> u128 {
+        self.amount.0 + self.safety_deposit.0
+    }
 
-        let cleaned_address = if address.starts_with("0x") {
-            &address[2..]
-        } else {
-            address
-        };
+    /// Check if the escrow locks native NEAR (as opposed to a NEP-141 token)
+    pub fn is_native(&self) -> bool {
+        self.token.is_none()
+    }
 
-        require!(
-            cleaned_address.len() == 40,
-            "EVM address must be 20 bytes (40 hex characters)"
-        );
+    /// Get the NEAR that must be attached on creation
+    /// For token escrows only the safety deposit is paid in NEAR, the amount arrives via ft_transfer_call
+    pub fn get_required_native_deposit(&self) -> u128 {
+        if self.is_native() {
+            self.get_total_required()
+        } else {
+            self.safety_deposit.0
+        }
+    }
 
+    /// Decode a secret given as 32-byte hex string (same as bytes32 secret on EVM)
+    /// Panics if the secret is not exactly 32 bytes
+    pub fn parse_secret(secret: &str) -> Vec<u8> {
+        let bytes = hex::decode(secret.trim_start_matches("0x"))
+            .unwrap_or_else(|_| env::panic_str("Secret must be a hex string"));
         require!(
-            cleaned_address.chars().all(|c| c.is_ascii_hexdigit()),
-            "EVM address must contain only hex characters"
+            bytes.len() == 32,
+            "Secret must be 32 bytes (64 hex characters)"
         );
+        bytes
     }
 
-    /// Get total required deposit (amount + safety deposit)
-    pub fn get_total_required(&self) -> u128 {
-        self.amount.0 + self.safety_deposit.0
+    /// Hash the secret with the escrow's hash algorithm
+    pub fn hash_secret(&self, secret: &str) -> Vec<u8> {
+        self.hash_algorithm.hash(&Self::parse_secret(secret))
     }
 
     /// Verify secret against hashlock
     pub fn verify_secret(&self, secret: &str) -> bool {
-        let secret_hash = Self::compute_sha256(secret);
-        let expected_hash = if self.hashlock.starts_with("0x") {
-            self.hashlock.clone()
-        } else {
-            format!("0x{}", self.hashlock)
+        let secret_hash = format!("0x{}", hex::encode(self.hash_secret(secret)));
+
+        secret_hash == self.get_normalized_hashlock().to_lowercase()
+    }
+
+    /// Check if the escrow can be filled in parts with a Merkle tree of secrets
+    pub fn is_partial_fill(&self) -> bool {
+        self.parts_count.is_some()
+    }
+
+    /// Verify secret number `index` against the Merkle root stored in the hashlock
+    pub fn verify_secret_part(&self, index: u32, secret: &str, proof: &[String]) -> bool {
+        let leaf = merkle::leaf(self.hash_algorithm, index, &self.hash_secret(secret));
+        merkle::verify(self.hash_algorithm, &self.hashlock, leaf, proof)
+    }
+
+    /// Get the part of the amount released by secret number `index`
+    pub fn get_part_amount(&self, index: u32) -> u128 {
+        match self.parts_count {
+            Some(parts) => merkle::part_amount(self.amount.0, index, parts),
+            None => self.amount.0,
+        }
+    }
+
+    /// Get the part of the safety deposit paid to whoever fills part `index`
+    pub fn get_part_safety_deposit(&self, index: u32) -> u128 {
+        match self.parts_count {
+            Some(parts) => merkle::part_amount(self.safety_deposit.0, index, parts),
+            None => self.safety_deposit.0,
+        }
+    }
+
+    /// Canonical hash of the immutables: keccak256 over the fields in declaration order
+    /// - order_hash, hashlock: raw bytes (32, 32), hex decoded so 0x / case don't matter
+    /// - hash_algorithm: 1 byte (0 = sha256, 1 = keccak256)
+    /// - parts_count: u32 big-endian, 0 for a single secret
+    /// - maker, maker_public_key, token: u32 big-endian length followed by the account id / key bytes
+    ///   (length 0 for no key / native NEAR), keys include their curve type byte
+    /// - taker_address: 1 byte chain (0 = EVM, 1 = Tezos, 2 = NEAR), u32 big-endian length and the raw
+    ///   address (20 bytes for EVM, prefix + hash without checksum for Tezos, account id for NEAR)
+    /// - amount, safety_deposit: u128 big-endian
+    pub fn hash(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(160);
+        data.extend_from_slice(&Self::decode_hex(&self.order_hash));
+        data.extend_from_slice(&Self::decode_hex(&self.hashlock));
+        data.push(match self.hash_algorithm {
+            HashAlgorithm::Sha256 => 0,
+            HashAlgorithm::Keccak256 => 1,
+        });
+        data.extend_from_slice(&self.parts_count.unwrap_or(0).to_be_bytes());
+        let fields = [
+            Some(self.maker.as_bytes()),
+            self.maker_public_key.as_ref().map(|key| key.as_bytes()),
+            self.token.as_ref().map(|token| token.as_bytes()),
+        ];
+        for field in fields {
+            let bytes = field.unwrap_or_default();
+            data.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+            data.extend_from_slice(bytes);
+        }
+        let taker = self.taker_address.to_bytes();
+        data.push(self.taker_address.chain_id());
+        data.extend_from_slice(&(taker.len() as u32).to_be_bytes());
+        data.extend_from_slice(&taker);
+        data.extend_from_slice(&self.amount.0.to_be_bytes());
+        data.extend_from_slice(&self.safety_deposit.0.to_be_bytes());
+        env::keccak256(&data)
+    }
+
+    /// Hash of the immutables as ImmutablesLib.hash computes it on EVM:
+    /// keccak256(abi.encode(orderHash, hashlock, maker, taker, token, amount, safetyDeposit, timelocks))
+    /// NEAR accounts have no EVM form, so the maker / token addresses used on the EVM leg are passed in
+    /// (zero address for native) together with the packed TimelocksLib value as a 32-byte hex word
+    pub fn hash_evm(
+        &self,
+        maker_evm_address: &str,
+        token_evm_address: &str,
+        timelocks: &str,
+    ) -> Vec<u8> {
+        // Same 20-byte / checksum rules as an EVM taker address
+        let evm_address = |address: &str| {
+            let address = ChainAddress::Evm(address.to_string());
+            address.validate();
+            address.to_bytes()
         };
 
-        secret_hash.to_lowercase() == expected_hash.to_lowercase()
+        let mut data = Vec::with_capacity(256);
+        data.extend_from_slice(&Self::abi_word(&Self::decode_hex(&self.order_hash)));
+        data.extend_from_slice(&Self::abi_word(&Self::decode_hex(&self.hashlock)));
+        data.extend_from_slice(&Self::abi_word(&evm_address(maker_evm_address)));
+        let taker = self
+            .get_normalized_evm_address()
+            .unwrap_or_else(|| env::panic_str("Taker is not an EVM address"));
+        data.extend_from_slice(&Self::abi_word(&Self::decode_hex(&taker)));
+        data.extend_from_slice(&Self::abi_word(&evm_address(token_evm_address)));
+        data.extend_from_slice(&Self::abi_word(&self.amount.0.to_be_bytes()));
+        data.extend_from_slice(&Self::abi_word(&self.safety_deposit.0.to_be_bytes()));
+        data.extend_from_slice(&Self::abi_word(&Self::decode_hex(timelocks)));
+        env::keccak256(&data)
+    }
+
+    /// Left-pad a big-endian value to a 32-byte ABI word
+    fn abi_word(value: &[u8]) -> [u8; 32] {
+        require!(value.len() <= 32, "Value does not fit in 32 bytes");
+        let mut word = [0u8; 32];
+        word[32 - value.len()..].copy_from_slice(value);
+        word
+    }
+
+    fn decode_hex(value: &str) -> Vec<u8> {
+        hex::decode(value.trim_start_matches("0x"))
+            .unwrap_or_else(|_| env::panic_str("Invalid hex string"))
     }
 
     /// Compute SHA256 hash of input string
@@ -154,12 +319,8 @@ impl EscrowImmutables {
         }
     }
 
-    /// Get normalized EVM address (with 0x prefix)
-    pub fn get_normalized_evm_address(&self) -> String {
-        if self.taker_evm_address.starts_with("0x") {
-            self.taker_evm_address.clone()
-        } else {
-            format!("0x{}", self.taker_evm_address)
-        }
+    /// Get normalized taker EVM address (with 0x prefix), None if the taker is on another chain
+    pub fn get_normalized_evm_address(&self) -> Option<String> {
+        self.taker_address.as_evm()
     }
 }
@@ -3,16 +3,81 @@ use near_sdk::borsh;
 // Factory contract will be used to deploy this
 use near_sdk::json_types::U128;
 use near_sdk::{
-    env, log, near, require,
+    env, near, require,
     serde::{Deserialize, Serialize},
-    AccountId, NearToken, PanicOnDefault, Promise,
+    AccountId, NearToken, PanicOnDefault, Promise, PromiseError, PromiseOrValue, PromiseResult,
+    PublicKey,
 };
 
+mod authorization;
+mod chain_address;
+mod events;
 mod immutables;
+mod merkle;
 mod timelocks;
+mod token;
+mod view;
 
-pub use immutables::EscrowImmutables;
-pub use timelocks::{TimelockStage, Timelocks};
+use events::EscrowEvent;
+pub use authorization::WithdrawalAuthorization;
+pub use chain_address::ChainAddress;
+pub use immutables::{EscrowImmutables, HashAlgorithm};
+pub use timelocks::{StageBounds, TimelockMode, TimelockPolicy, TimelockStage, Timelocks};
+use token::{
+    ext_ft, GAS_FOR_FT_TRANSFER, GAS_FOR_RESOLVE_NATIVE_PAYOUTS, GAS_FOR_RESOLVE_PAYOUT,
+    GAS_FOR_RESOLVE_RESCUE, ONE_YOCTO,
+};
+pub use token::{NativePayout, PayoutKind};
+pub use view::{BalancesView, EscrowView, StageTime, TimelocksView};
+
+/// Default delay after creation before the depositor can rescue funds (7 days, after the longest lock the default policy allows)
+pub const DEFAULT_RESCUE_DELAY: u64 = 86400 * 7;
+
+/// Which leg of the swap this escrow is (same split as EvmEscrow / EvmEscrowDst on EVM)
+#[near(serializers = [json,borsh])]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EscrowKind {
+    /// Maker locks funds, taker withdraws them with the secret, cancellation refunds the maker
+    Source,
+    /// Resolver locks funds, maker receives them with the secret, cancellation refunds the depositor
+    Destination,
+}
+
+/// Lifecycle of an escrow
+/// Active -> Withdrawn | Cancelled | Rescued -> Closed
+/// Withdrawn / Cancelled / Rescued go back to Active only when their transfer failed and was rolled back
+#[near(serializers = [json,borsh])]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EscrowState {
+    /// Funds locked, waiting for the secret or the cancellation timelock
+    Active,
+    /// Amount released with the secret (all parts filled for partial fills)
+    Withdrawn,
+    /// Funds returned to the maker / depositor
+    Cancelled,
+    /// Depositor took the locked funds with rescue_funds after the rescue delay
+    Rescued,
+    /// Account deleted, nothing left in the escrow
+    Closed,
+}
+
+impl EscrowState {
+    pub fn can_transition_to(&self, next: EscrowState) -> bool {
+        use EscrowState::*;
+        matches!(
+            (self, next),
+            (Active, Withdrawn | Cancelled | Rescued)
+                | (Withdrawn | Cancelled | Rescued, Active)
+                | (Withdrawn | Cancelled | Rescued, Closed)
+        )
+    }
+
+    pub fn is_final(&self) -> bool {
+        !matches!(self, EscrowState::Active)
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 #[near(contract_state)]
@@ -23,13 +88,32 @@ pub struct HTLCEscrow {
 
     // Timelock configuration
     pub timelocks: Timelocks,
+    // Seconds after creation before rescue_funds is allowed (RESCUE_DELAY on EVM)
+    pub rescue_delay: u64,
+
+    pub kind: EscrowKind,
+    // NEAR account of the taker (resolver), only set for source escrows
+    pub taker: Option<AccountId>,
+    // Accounts allowed to withdraw during the private withdrawal period
+    pub authorized_resolvers: Vec<AccountId>,
 
     pub deposited_amount: U128,
     // depositor aka the resolver that deposits in the src chain
     pub depositor: AccountId,
-    pub is_withdrawn: bool,
-    pub is_cancelled: bool,
+    pub state: EscrowState,
     pub revealed_secret: Option<String>,
+
+    // Partial fills only: amount released so far and the secret indices already used
+    pub filled_amount: U128,
+    pub filled_parts: Vec<u32>,
+
+    // NEP-141 escrows only: tokens currently held and the account that sent them
+    pub token_balance: U128,
+    pub token_funder: Option<AccountId>,
+    // Nonces of the maker-signed withdrawal authorizations already used
+    pub used_nonces: Vec<u64>,
+    // Payouts waiting for resolve_payout (the escrow can't be closed until they settle)
+    pub pending_payouts: u32,
 }
 
 // impl Default for HTLCEscrow {
@@ -45,99 +129,231 @@ impl HTLCEscrow {
     pub fn new(
         order_hash: String,
         hashlock: String,
+        hash_algorithm: Option<HashAlgorithm>,
+        parts_count: Option<u32>,
         maker: AccountId,
-        taker_evm_address: String,
+        maker_public_key: Option<PublicKey>,
+        taker_address: ChainAddress,
+        token: Option<AccountId>,
         amount: U128,
         safety_deposit: U128,
-        withdrawal_timelock: u64,
-        public_withdrawal_timelock: u64,
-        cancellation_timelock: u64,
+        withdrawal_timelock: Option<u64>,
+        public_withdrawal_timelock: Option<u64>,
+        cancellation_timelock: Option<u64>,
+        public_cancellation_timelock: Option<u64>,
+        packed_timelocks: Option<String>,
+        timelock_mode: Option<TimelockMode>,
+        timelock_policy: Option<TimelockPolicy>,
+        rescue_delay: Option<u64>,
+        depositor: Option<AccountId>,
+        authorized_resolvers: Option<Vec<AccountId>>,
     ) -> Self {
-        let deposited = env::attached_deposit().as_yoctonear();
-
         // Create and validate immutables
         let immutables = EscrowImmutables::new(
-            order_hash.clone(),
+            order_hash,
             hashlock,
-            maker.clone(),
-            taker_evm_address,
+            hash_algorithm.unwrap_or_default(),
+            parts_count,
+            maker,
+            maker_public_key,
+            taker_address,
+            token,
             amount,
             safety_deposit,
         );
 
-        // Validate deposit amount
-        let required_amount = immutables.get_total_required();
-        require!(deposited >= required_amount, "Insufficient deposit");
-
         // Create and validate timelocks
-        let timelocks = Timelocks::new(
+        let timelocks = Timelocks::from_init_args(
+            timelock_mode.unwrap_or_default(),
+            packed_timelocks,
             withdrawal_timelock,
             public_withdrawal_timelock,
             cancellation_timelock,
+            public_cancellation_timelock,
         );
+        timelocks.require_policy(&timelock_policy.unwrap_or_default());
+
+        Self::create(
+            EscrowKind::Destination,
+            immutables,
+            timelocks,
+            rescue_delay,
+            depositor,
+            authorized_resolvers,
+            None,
+        )
+    }
 
-        log!(
-            "HTLC Escrow created: order_hash={}, amount={}, maker={}",
+    /// Source chain escrow: the maker's funds are locked here and released to the taker
+    #[init]
+    #[payable]
+    pub fn new_src(
+        order_hash: String,
+        hashlock: String,
+        hash_algorithm: Option<HashAlgorithm>,
+        parts_count: Option<u32>,
+        maker: AccountId,
+        maker_public_key: Option<PublicKey>,
+        taker: AccountId,
+        taker_address: ChainAddress,
+        token: Option<AccountId>,
+        amount: U128,
+        safety_deposit: U128,
+        withdrawal_timelock: Option<u64>,
+        public_withdrawal_timelock: Option<u64>,
+        cancellation_timelock: Option<u64>,
+        public_cancellation_timelock: Option<u64>,
+        packed_timelocks: Option<String>,
+        timelock_mode: Option<TimelockMode>,
+        timelock_policy: Option<TimelockPolicy>,
+        rescue_delay: Option<u64>,
+        depositor: Option<AccountId>,
+        authorized_resolvers: Option<Vec<AccountId>>,
+    ) -> Self {
+        let immutables = EscrowImmutables::new(
             order_hash,
-            deposited,
-            maker
+            hashlock,
+            hash_algorithm.unwrap_or_default(),
+            parts_count,
+            maker,
+            maker_public_key,
+            taker_address,
+            token,
+            amount,
+            safety_deposit,
         );
 
-        Self {
+        let timelocks = Timelocks::from_init_args(
+            timelock_mode.unwrap_or_default(),
+            packed_timelocks,
+            withdrawal_timelock,
+            public_withdrawal_timelock,
+            cancellation_timelock,
+            public_cancellation_timelock,
+        );
+        timelocks.require_policy(&timelock_policy.unwrap_or_default());
+
+        Self::create(
+            EscrowKind::Source,
             immutables,
             timelocks,
-            deposited_amount: U128(deposited),
-            depositor: env::predecessor_account_id(),
-            is_withdrawn: false,
-            is_cancelled: false,
-            revealed_secret: None,
+            rescue_delay,
+            depositor,
+            authorized_resolvers,
+            Some(taker),
+        )
+    }
+
+    /// NEP-141 receiver hook - funds a token escrow via ft_transfer_call
+    /// Any amount above immutables.amount is returned to the sender
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let _ = msg;
+        require!(
+            self.immutables.token.as_ref() == Some(&env::predecessor_account_id()),
+            "Token not accepted by this escrow"
+        );
+        require!(self.is_active(), "Escrow is not active");
+        require!(self.token_balance.0 == 0, "Escrow already funded");
+        require!(
+            amount.0 >= self.immutables.amount.0,
+            "Insufficient token amount"
+        );
+
+        EscrowEvent::EscrowFunded {
+            order_hash: &self.immutables.order_hash,
+            token: &env::predecessor_account_id(),
+            amount: self.immutables.amount,
+            funder: &sender_id,
         }
+        .emit();
+
+        self.token_balance = self.immutables.amount;
+        self.token_funder = Some(sender_id);
+
+        PromiseOrValue::Value(U128(amount.0 - self.immutables.amount.0))
     }
 
     /// Withdraw funds by revealing the secret (equivalent to withdraw in Solidity)
     /// No order_hash needed - this contract IS the escrow
+    /// Every withdrawal path closes once the cancellation timelock opens (onlyBefore(Cancellation) on EVM)
     pub fn withdraw(&mut self, secret: String) -> Promise {
-        require!(!self.is_withdrawn, "Already withdrawn");
-        require!(!self.is_cancelled, "Escrow cancelled");
-
-        // Verify secret matches hashlock using immutables
-        require!(self.immutables.verify_secret(&secret), "Invalid secret");
-
-        // Check timelock using timelocks module
-        self.timelocks.require_timelock(TimelockStage::Withdrawal);
+        self.require_withdrawal_access();
 
-        // Update state
-        self.is_withdrawn = true;
-        self.revealed_secret = Some(secret.clone());
+        let recipient = self.withdrawal_recipient();
+        self.withdraw_funds(secret, recipient)
+    }
 
-        log!(
-            "HTLC Withdrawal: order_hash={}, secret={}, taker_evm={}",
-            self.immutables.order_hash,
-            secret,
-            self.immutables.taker_evm_address
+    /// Withdraw funds to another account (equivalent to withdrawTo in Solidity)
+    /// Only the account the funds belong to can redirect them, e.g. to a custody or hot wallet:
+    /// the taker on source escrows, the maker on destination escrows (resolvers never can)
+    /// The owner may do so from the start of the withdrawal period, the authorized resolver
+    /// check of the private period doesn't apply to them
+    pub fn withdraw_to(&mut self, secret: String, recipient: AccountId) -> Promise {
+        require!(
+            env::predecessor_account_id() == self.withdrawal_recipient(),
+            "Only the withdrawal recipient can choose another recipient"
         );
 
-        // Transfer funds to maker
-        Promise::new(self.immutables.maker.clone()).transfer(NearToken::from_yoctonear(
-            self.immutables.amount.0 ,
-        ))
+        self.withdraw_funds(secret, recipient)
     }
 
-    /// withdrawing to a special address if required
-    /// helper function in the contract mostly
-    fn withdrawTo(&mut self, secret: String, address: AccountId) -> Promise {
-        require!(self.immutables.verify_secret(&secret), "Invalid secret");
-        self.is_withdrawn = true;
-        self.revealed_secret = Some(secret.clone());
+    /// Withdraw on behalf of the maker of a destination escrow (meta-transaction)
+    /// Any relayer can submit the secret with an authorization signed by maker_public_key
+    /// (passed by the maker itself, or registered by the maker with the factory),
+    /// the maker's consent replaces the authorized resolver check of the private period
+    /// and the relayer earns the safety deposit for paying the gas
+    pub fn withdraw_with_authorization(
+        &mut self,
+        secret: String,
+        authorization: WithdrawalAuthorization,
+        signature: String,
+    ) -> Promise {
+        require!(
+            self.kind == EscrowKind::Destination,
+            "Only destination escrows pay out to the maker"
+        );
+        let public_key = self
+            .immutables
+            .maker_public_key
+            .as_ref()
+            .unwrap_or_else(|| env::panic_str("Escrow has no maker public key"));
+        require!(
+            authorization.escrow_id == env::current_account_id(),
+            "Authorization is for another escrow"
+        );
+        require!(
+            Timelocks::get_current_timestamp() < authorization.expires_at,
+            "Authorization expired"
+        );
+        require!(
+            !self.used_nonces.contains(&authorization.nonce),
+            "Authorization nonce already used"
+        );
+        require!(
+            authorization.verify(public_key, &signature),
+            "Invalid authorization signature"
+        );
+
+        require!(!self.timelocks.can_cancel(), "Withdrawal period is over");
 
-        Promise::new(address).transfer(NearToken::from_yoctonear(self.immutables.amount.0))
+        self.used_nonces.push(authorization.nonce);
+        self.withdraw_funds(secret, authorization.recipient)
     }
 
     /// Public Withdrawal
-    /// anyone with the secret can call this function to unlock the funds to the maker
+    /// anyone with the secret can call this function to unlock the funds to the maker (taker on source escrows)
     pub fn publicwithdraw(&mut self, secret: String) -> Promise {
-        require!(!self.is_withdrawn, "Already withdrawn");
-        require!(!self.is_cancelled, "Escrow cancelled");
+        self.require_active();
+        require!(self.is_funded(), "Escrow not funded");
+        require!(
+            !self.immutables.is_partial_fill(),
+            "Escrow is filled in parts, use withdraw_part"
+        );
 
         // Verify secret matches hashlock using immutables
         require!(self.immutables.verify_secret(&secret), "Invalid secret");
@@ -145,44 +361,616 @@ impl HTLCEscrow {
         // Check timelock using timelocks module
         self.timelocks
             .require_timelock(TimelockStage::PublicWithdrawal);
+        require!(!self.timelocks.can_cancel(), "Withdrawal period is over");
 
         // Update state
-        self.is_withdrawn = true;
+        self.transition(EscrowState::Withdrawn);
         self.revealed_secret = Some(secret.clone());
 
-        log!(
-            "HTLC Withdrawal: order_hash={}, secret={}, taker_evm={}",
-            self.immutables.order_hash,
-            secret,
-            self.immutables.taker_evm_address
+        let recipient = self.withdrawal_recipient();
+        self.emit_withdrawn(&secret, None, self.immutables.amount, &recipient);
+
+        // Transfer funds to maker (taker on source escrows), safety deposit to the caller
+        self.settle(
+            recipient,
+            self.immutables.amount.0,
+            self.immutables.safety_deposit.0,
+            env::predecessor_account_id(),
+            PayoutKind::Withdrawal,
+        )
+    }
+
+    /// Withdraw one part of a partially fillable escrow by revealing secret number `index`
+    /// `proof` holds the sibling hashes from the leaf up to the Merkle root stored as hashlock
+    /// Each index can be used once, the escrow is withdrawn when all parts are filled
+    pub fn withdraw_part(&mut self, index: u32, secret: String, proof: Vec<String>) -> Promise {
+        self.require_active();
+        require!(self.is_funded(), "Escrow not funded");
+
+        let parts = self
+            .immutables
+            .parts_count
+            .unwrap_or_else(|| env::panic_str("Escrow is not filled in parts, use withdraw"));
+        require!(index < parts, "Secret index out of range");
+        require!(
+            !self.filled_parts.contains(&index),
+            "Secret index already used"
         );
 
-        // Transfer funds to maker
-        Promise::new(self.immutables.maker.clone())
-            .transfer(NearToken::from_yoctonear(self.immutables.amount.0))
+        self.require_withdrawal_access();
+
+        // Verify secret is a leaf of the Merkle root
+        require!(
+            self.immutables.verify_secret_part(index, &secret, &proof),
+            "Invalid secret or proof"
+        );
+
+        // Check timelock using timelocks module
+        self.timelocks.require_timelock(TimelockStage::Withdrawal);
+        require!(!self.timelocks.can_cancel(), "Withdrawal period is over");
+
+        // Update state
+        let part_amount = self.immutables.get_part_amount(index);
+        self.filled_parts.push(index);
+        self.filled_amount = U128(self.filled_amount.0 + part_amount);
+        self.revealed_secret = Some(secret.clone());
+        if self.filled_parts.len() as u32 == parts {
+            self.transition(EscrowState::Withdrawn);
+        }
+
+        let recipient = self.withdrawal_recipient();
+        self.emit_withdrawn(&secret, Some(index), U128(part_amount), &recipient);
+
+        // Each part also releases its share of the safety deposit to the caller
+        self.settle(
+            recipient,
+            part_amount,
+            self.immutables.get_part_safety_deposit(index),
+            env::predecessor_account_id(),
+            PayoutKind::PartialWithdrawal(index),
+        )
     }
 
     /// Cancel escrow and return funds (equivalent to cancel in Solidity)
     /// No order_hash needed - this contract IS the escrow
+    /// Private cancellation: only the depositor (taker on source escrows) can cancel
     pub fn cancel(&mut self) -> Promise {
-        require!(!self.is_withdrawn, "Already withdrawn");
-        require!(!self.is_cancelled, "Already cancelled");
+        match self.kind {
+            EscrowKind::Source => self.require_taker(),
+            EscrowKind::Destination => require!(
+                env::predecessor_account_id() == self.depositor,
+                "Only the depositor can cancel during the private cancellation period"
+            ),
+        }
 
         // Check cancellation timelock using timelocks module
         self.timelocks.require_timelock(TimelockStage::Cancellation);
 
+        self.refund(false)
+    }
+
+    /// Public cancellation
+    /// anyone can call this function to return the funds and earn the safety deposit
+    pub fn public_cancel(&mut self) -> Promise {
+        self.timelocks
+            .require_timelock(TimelockStage::PublicCancellation);
+
+        self.refund(false)
+    }
+
+    /// Emergency refund (equivalent to emergency_refund on Tezos)
+    /// Last resort once the emergency timelock (24 hours after cancellation) has passed and nobody cancelled.
+    /// Anyone can call it, but nothing goes to the caller: the remaining amount and the rest of the
+    /// safety deposit return to whoever locked the funds (the maker on source escrows, the
+    /// depositor / token funder on destination escrows), any excess deposit to the depositor
+    pub fn emergency_refund(&mut self) -> Promise {
+        require!(
+            self.timelocks.can_emergency_refund(),
+            "Emergency refund timelock not met"
+        );
+
+        self.refund(true)
+    }
+
+    /// Rescue funds stuck in the escrow (equivalent to rescueFunds in Solidity)
+    /// Only the depositor can call it, once the rescue delay after creation has passed
+    /// `token` is the NEP-141 contract to rescue from, None for native NEAR
+    pub fn rescue_funds(&mut self, token: Option<AccountId>, amount: U128) -> Promise {
+        require!(
+            env::predecessor_account_id() == self.depositor,
+            "Only the depositor can rescue funds"
+        );
+        require!(
+            self.timelocks.can_rescue(self.rescue_delay),
+            "Rescue delay not met"
+        );
+
+        // Taking the escrow's own asset while it is still active ends the escrow
+        let escrow_asset = match &token {
+            None => self.immutables.is_native(),
+            Some(token) => self.immutables.token.as_ref() == Some(token),
+        };
+        let ends_escrow = escrow_asset && self.state == EscrowState::Active;
+        if ends_escrow {
+            self.transition(EscrowState::Rescued);
+        }
+
+        EscrowEvent::FundsRescued {
+            order_hash: &self.immutables.order_hash,
+            token: token.as_ref(),
+            amount,
+            recipient: &self.depositor,
+        }
+        .emit();
+
+        let transfer = match token.clone() {
+            None => {
+                Promise::new(self.depositor.clone()).transfer(NearToken::from_yoctonear(amount.0))
+            }
+            Some(token) => {
+                if escrow_asset {
+                    self.token_balance = U128(self.token_balance.0.saturating_sub(amount.0));
+                }
+                ext_ft::ext(token)
+                    .with_attached_deposit(ONE_YOCTO)
+                    .with_static_gas(GAS_FOR_FT_TRANSFER)
+                    .ft_transfer(self.depositor.clone(), amount, None)
+            }
+        };
+
+        self.pending_payouts += 1;
+        transfer.then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_RESOLVE_RESCUE)
+                .resolve_rescue(token, amount, escrow_asset, ends_escrow),
+        )
+    }
+
+    /// Delete the escrow account once it is finalized and nothing is owed anymore
+    /// The remaining balance (storage stake and dust) goes to the depositor, anyone can call it
+    pub fn close(&mut self) -> Promise {
+        require!(
+            self.state.can_transition_to(EscrowState::Closed),
+            "Escrow is not withdrawn, cancelled or rescued yet"
+        );
+        require!(self.pending_payouts == 0, "Payouts are still in flight");
+        require!(
+            self.token_balance.0 == 0,
+            "Escrow still holds tokens, use rescue_funds first"
+        );
+
+        self.transition(EscrowState::Closed);
+        EscrowEvent::EscrowClosed {
+            order_hash: &self.immutables.order_hash,
+            beneficiary: &self.depositor,
+        }
+        .emit();
+
+        Promise::new(env::current_account_id()).delete_account(self.depositor.clone())
+    }
+
+    /// Callback for the main payout of withdrawals and cancellations (NEAR or NEP-141)
+    /// The transfer fails if the receiver account doesn't exist, or for ft_transfer if it is not
+    /// registered with the token (no storage deposit); the funds then stay in the escrow and the
+    /// action is rolled back so it can be retried (or the funds rescued)
+    /// The NEAR side payouts (safety deposit, excess deposit) are only sent once the funds went out
+    #[private]
+    pub fn resolve_payout(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        kind: PayoutKind,
+        native_payouts: Vec<NativePayout>,
+        #[callback_result] transfer_result: Result<(), PromiseError>,
+    ) -> PromiseOrValue<bool> {
+        self.pending_payouts -= 1;
+        if transfer_result.is_ok() {
+            return match Self::pay_native(native_payouts) {
+                Some(promise) => PromiseOrValue::Promise(promise),
+                None => PromiseOrValue::Value(true),
+            };
+        }
+
+        if !self.immutables.is_native() {
+            self.token_balance = U128(self.token_balance.0 + amount.0);
+        }
+        match kind {
+            PayoutKind::Withdrawal | PayoutKind::Cancellation => {
+                self.transition(EscrowState::Active)
+            }
+            PayoutKind::PartialWithdrawal(index) => {
+                self.filled_parts.retain(|part| *part != index);
+                self.filled_amount = U128(self.filled_amount.0 - amount.0);
+                // Only the last part moved the escrow to Withdrawn
+                if self.state == EscrowState::Withdrawn {
+                    self.transition(EscrowState::Active);
+                }
+            }
+        }
+
+        EscrowEvent::PayoutFailed {
+            order_hash: &self.immutables.order_hash,
+            receiver: &receiver_id,
+            token: self.immutables.token.as_ref(),
+            amount,
+            kind: Some(kind),
+            rolled_back: true,
+        }
+        .emit();
+        PromiseOrValue::Value(false)
+    }
+
+    /// Callback for rescue_funds transfers
+    /// On failure the rescued funds stay in the escrow: the token balance is restored and an
+    /// escrow ended by the rescue is active again
+    #[private]
+    pub fn resolve_rescue(
+        &mut self,
+        token: Option<AccountId>,
+        amount: U128,
+        escrow_asset: bool,
+        ends_escrow: bool,
+        #[callback_result] transfer_result: Result<(), PromiseError>,
+    ) -> bool {
+        self.pending_payouts -= 1;
+        if transfer_result.is_ok() {
+            return true;
+        }
+
+        if escrow_asset && token.is_some() {
+            self.token_balance = U128(self.token_balance.0 + amount.0);
+        }
+        if ends_escrow {
+            self.transition(EscrowState::Active);
+        }
+
+        EscrowEvent::PayoutFailed {
+            order_hash: &self.immutables.order_hash,
+            receiver: &self.depositor,
+            token: token.as_ref(),
+            amount,
+            kind: None,
+            rolled_back: true,
+        }
+        .emit();
+        false
+    }
+
+    /// Callback for the NEAR side payouts, one promise result per non-zero payout
+    /// A failed side payout can't be rolled back (the escrow is already final), the NEAR stays
+    /// in the escrow and the depositor can take it with rescue_funds
+    #[private]
+    pub fn resolve_native_payouts(&mut self, native_payouts: Vec<NativePayout>) -> bool {
+        let mut all_paid = true;
+        let payouts = native_payouts.iter().filter(|payout| payout.amount.0 > 0);
+        for (index, payout) in payouts.enumerate() {
+            if let PromiseResult::Successful(_) = env::promise_result(index as u64) {
+                continue;
+            }
+            all_paid = false;
+            EscrowEvent::PayoutFailed {
+                order_hash: &self.immutables.order_hash,
+                receiver: &payout.receiver_id,
+                token: None,
+                amount: payout.amount,
+                kind: None,
+                rolled_back: false,
+            }
+            .emit();
+        }
+        all_paid
+    }
+
+    fn create(
+        kind: EscrowKind,
+        immutables: EscrowImmutables,
+        timelocks: Timelocks,
+        rescue_delay: Option<u64>,
+        depositor: Option<AccountId>,
+        authorized_resolvers: Option<Vec<AccountId>>,
+        taker: Option<AccountId>,
+    ) -> Self {
+        let deposited = env::attached_deposit().as_yoctonear();
+        // Factories pass the account that paid for the escrow, otherwise it is the caller
+        let depositor = depositor.unwrap_or_else(env::predecessor_account_id);
+
+        // Default resolver is whoever withdraws on this chain: the taker (source) or the depositor (destination)
+        let authorized_resolvers = authorized_resolvers
+            .unwrap_or_else(|| vec![taker.clone().unwrap_or_else(|| depositor.clone())]);
+        require!(
+            !authorized_resolvers.is_empty(),
+            "At least one authorized resolver is required"
+        );
+
+        // Rescue must not open before the funds can be cancelled the regular way
+        let rescue_delay = rescue_delay.unwrap_or(DEFAULT_RESCUE_DELAY);
+        require!(
+            timelocks.rescue_start(rescue_delay) > timelocks.public_cancellation_timelock,
+            "Rescue delay must end after the public cancellation timelock"
+        );
+
+        // The maker key authorizes withdrawals to any recipient, so it must come from the maker:
+        // either the maker deploys the escrow, or the factory passes the key the maker registered there
+        if immutables.maker_public_key.is_some() {
+            let predecessor = env::predecessor_account_id();
+            require!(
+                predecessor == immutables.maker
+                    || env::current_account_id().is_sub_account_of(&predecessor),
+                "Only the maker or the escrow factory can set the maker public key"
+            );
+        }
+
+        // Validate deposit amount (token escrows only attach the safety deposit)
+        let required_amount = immutables.get_required_native_deposit();
+        require!(deposited >= required_amount, "Insufficient deposit");
+
+        EscrowEvent::EscrowCreated {
+            order_hash: &immutables.order_hash,
+            hashlock: &immutables.hashlock,
+            maker: &immutables.maker,
+            token: immutables.token.as_ref(),
+            amount: immutables.amount,
+            safety_deposit: immutables.safety_deposit,
+            deposit: U128(deposited),
+        }
+        .emit();
+
+        Self {
+            immutables,
+            timelocks,
+            rescue_delay,
+            kind,
+            taker,
+            authorized_resolvers,
+            deposited_amount: U128(deposited),
+            depositor,
+            state: EscrowState::Active,
+            revealed_secret: None,
+            filled_amount: U128(0),
+            filled_parts: Vec::new(),
+            token_balance: U128(0),
+            token_funder: None,
+            used_nonces: Vec::new(),
+            pending_payouts: 0,
+        }
+    }
+
+    fn emit_withdrawn(
+        &self,
+        secret: &str,
+        part_index: Option<u32>,
+        amount: U128,
+        recipient: &AccountId,
+    ) {
+        EscrowEvent::EscrowWithdrawn {
+            order_hash: &self.immutables.order_hash,
+            hashlock: &self.immutables.hashlock,
+            secret,
+            part_index,
+            amount,
+            recipient,
+            taker_address: &self.immutables.taker_address,
+        }
+        .emit();
+    }
+
+    /// Private period withdrawal of the whole amount to `recipient`, safety deposit to the caller
+    /// Callers check who may withdraw first
+    fn withdraw_funds(&mut self, secret: String, recipient: AccountId) -> Promise {
+        self.require_active();
+        require!(self.is_funded(), "Escrow not funded");
+        require!(
+            !self.immutables.is_partial_fill(),
+            "Escrow is filled in parts, use withdraw_part"
+        );
+
+        // Verify secret matches hashlock using immutables
+        require!(self.immutables.verify_secret(&secret), "Invalid secret");
+
+        // Check timelock using timelocks module
+        self.timelocks.require_timelock(TimelockStage::Withdrawal);
+        require!(!self.timelocks.can_cancel(), "Withdrawal period is over");
+
         // Update state
-        self.is_cancelled = true;
+        self.transition(EscrowState::Withdrawn);
+        self.revealed_secret = Some(secret.clone());
+
+        self.emit_withdrawn(&secret, None, self.immutables.amount, &recipient);
+
+        self.settle(
+            recipient,
+            self.immutables.amount.0,
+            self.immutables.safety_deposit.0,
+            env::predecessor_account_id(),
+            PayoutKind::Withdrawal,
+        )
+    }
+
+    fn require_active(&self) {
+        match self.state {
+            EscrowState::Active => {}
+            EscrowState::Withdrawn => env::panic_str("Already withdrawn"),
+            EscrowState::Cancelled => env::panic_str("Escrow cancelled"),
+            EscrowState::Rescued => env::panic_str("Escrow funds rescued"),
+            EscrowState::Closed => env::panic_str("Escrow closed"),
+        }
+    }
+
+    fn transition(&mut self, next: EscrowState) {
+        require!(
+            self.state.can_transition_to(next),
+            format!(
+                "Invalid state transition from {:?} to {:?}",
+                self.state, next
+            )
+        );
+        self.state = next;
+    }
 
-        log!(
-            "HTLC Cancelled: order_hash={}, refunded_to={}",
-            self.immutables.order_hash,
-            self.depositor
+    fn require_taker(&self) {
+        require!(
+            self.taker.as_ref() == Some(&env::predecessor_account_id()),
+            "Only the taker can call this method"
         );
+    }
+
+    /// Only authorized resolvers can withdraw until the public withdrawal period starts
+    fn require_withdrawal_access(&self) {
+        if !self.timelocks.can_public_withdraw() {
+            self.require_authorized_resolver();
+        }
+    }
+
+    fn require_authorized_resolver(&self) {
+        require!(
+            self.is_authorized_resolver(env::predecessor_account_id()),
+            "Only an authorized resolver can withdraw during the private withdrawal period"
+        );
+    }
+
+    /// Account that receives the amount once the secret is revealed
+    fn withdrawal_recipient(&self) -> AccountId {
+        match self.kind {
+            EscrowKind::Source => self.taker.clone().unwrap(),
+            EscrowKind::Destination => self.immutables.maker.clone(),
+        }
+    }
+
+    /// Account that gets the locked funds back on cancellation
+    /// Tokens of a destination escrow go back to whoever funded it
+    fn refund_recipient(&self) -> AccountId {
+        match self.kind {
+            EscrowKind::Source => self.immutables.maker.clone(),
+            EscrowKind::Destination => self
+                .token_funder
+                .clone()
+                .unwrap_or_else(|| self.depositor.clone()),
+        }
+    }
+
+    /// Return the locked funds, `emergency` also returns the safety deposit instead of paying the caller
+    fn refund(&mut self, emergency: bool) -> Promise {
+        self.require_active();
+
+        // Update state
+        self.transition(EscrowState::Cancelled);
+
+        let refund_to = self.refund_recipient();
+        let safety_deposit_to = if emergency {
+            refund_to.clone()
+        } else {
+            env::predecessor_account_id()
+        };
+
+        // Return what is still locked (parts may already be filled)
+        let remaining = if self.immutables.is_native() {
+            self.immutables.amount.0 - self.filled_amount.0
+        } else {
+            self.token_balance.0
+        };
+
+        let safety_deposit = self.immutables.safety_deposit.0 - self.get_paid_safety_deposit();
+        if emergency {
+            EscrowEvent::EmergencyRefunded {
+                order_hash: &self.immutables.order_hash,
+                hashlock: &self.immutables.hashlock,
+                amount: U128(remaining),
+                safety_deposit: U128(safety_deposit),
+                recipient: &refund_to,
+            }
+            .emit();
+        } else {
+            EscrowEvent::EscrowCancelled {
+                order_hash: &self.immutables.order_hash,
+                hashlock: &self.immutables.hashlock,
+                amount: U128(remaining),
+                recipient: &refund_to,
+            }
+            .emit();
+        }
 
-        // Refund to depositor
-        Promise::new(self.depositor.clone())
-            .transfer(NearToken::from_yoctonear(self.deposited_amount.0))
+        self.settle(
+            refund_to,
+            remaining,
+            safety_deposit,
+            safety_deposit_to,
+            PayoutKind::Cancellation,
+        )
+    }
+
+    /// Safety deposit paid out so far (only partial fills pay it before the escrow is final)
+    fn get_paid_safety_deposit(&self) -> u128 {
+        self.filled_parts
+            .iter()
+            .map(|index| self.immutables.get_part_safety_deposit(*index))
+            .sum()
+    }
+
+    /// Pay out `amount` to the recipient and `safety_deposit` to the executor (Fusion+ incentive, normally the caller)
+    /// Once the escrow is final the excess deposit is also returned to the depositor
+    fn settle(
+        &mut self,
+        receiver_id: AccountId,
+        amount: u128,
+        safety_deposit: u128,
+        safety_deposit_to: AccountId,
+        kind: PayoutKind,
+    ) -> Promise {
+        let mut native_payouts = vec![NativePayout {
+            receiver_id: safety_deposit_to,
+            amount: U128(safety_deposit),
+        }];
+        if self.state.is_final() {
+            native_payouts.push(NativePayout {
+                receiver_id: self.depositor.clone(),
+                amount: self.get_excess_deposit(),
+            });
+        }
+
+        let payout = match self.immutables.token.clone() {
+            Some(token) if amount > 0 => {
+                self.token_balance = U128(self.token_balance.0 - amount);
+                ext_ft::ext(token)
+                    .with_attached_deposit(ONE_YOCTO)
+                    .with_static_gas(GAS_FOR_FT_TRANSFER)
+                    .ft_transfer(receiver_id.clone(), U128(amount), None)
+            }
+            None if amount > 0 => {
+                Promise::new(receiver_id.clone()).transfer(NearToken::from_yoctonear(amount))
+            }
+            _ => {
+                // Nothing to pay at all (unfunded token escrow without safety deposit)
+                return Self::pay_native(native_payouts).unwrap_or_else(|| {
+                    Promise::new(receiver_id).transfer(NearToken::from_yoctonear(0))
+                });
+            }
+        };
+
+        self.pending_payouts += 1;
+        payout.then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_RESOLVE_PAYOUT)
+                .resolve_payout(receiver_id, U128(amount), kind, native_payouts),
+        )
+    }
+
+    /// Joint promise sending every non-zero NEAR payout, checked by resolve_native_payouts
+    fn pay_native(payouts: Vec<NativePayout>) -> Option<Promise> {
+        let transfers = payouts
+            .iter()
+            .filter(|payout| payout.amount.0 > 0)
+            .map(|payout| {
+                Promise::new(payout.receiver_id.clone())
+                    .transfer(NearToken::from_yoctonear(payout.amount.0))
+            })
+            .reduce(Promise::and)?;
+        Some(
+            transfers.then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_NATIVE_PAYOUTS)
+                    .resolve_native_payouts(payouts),
+            ),
+        )
     }
 
     /// View functions - like Solidity public variables
@@ -194,18 +982,93 @@ impl HTLCEscrow {
         self.immutables.hashlock.clone()
     }
 
+    /// Canonical hash of the immutables (see EscrowImmutables::hash for the encoding)
+    pub fn get_immutables_hash(&self) -> String {
+        format!("0x{}", hex::encode(self.immutables.hash()))
+    }
+
+    /// Immutables hash as ImmutablesLib.hash would compute it on EVM
+    /// `maker_evm_address` / `token_evm_address` are the EVM addresses used on the other leg,
+    /// `timelocks` the packed TimelocksLib value as 32-byte hex (defaults to this escrow's timelocks)
+    pub fn get_evm_immutables_hash(
+        &self,
+        maker_evm_address: String,
+        token_evm_address: String,
+        timelocks: Option<String>,
+    ) -> String {
+        let timelocks = timelocks.unwrap_or_else(|| self.timelocks.to_packed());
+        let hash = self
+            .immutables
+            .hash_evm(&maker_evm_address, &token_evm_address, &timelocks);
+        format!("0x{}", hex::encode(hash))
+    }
+
     pub fn get_maker(&self) -> AccountId {
         self.immutables.maker.clone()
     }
 
-    pub fn get_taker_evm_address(&self) -> String {
-        self.immutables.taker_evm_address.clone()
+    pub fn get_kind(&self) -> EscrowKind {
+        self.kind
+    }
+
+    pub fn get_taker(&self) -> Option<AccountId> {
+        self.taker.clone()
+    }
+
+    pub fn get_authorized_resolvers(&self) -> Vec<AccountId> {
+        self.authorized_resolvers.clone()
+    }
+
+    /// Check if the account can withdraw during the private withdrawal period
+    pub fn is_authorized_resolver(&self, account_id: AccountId) -> bool {
+        self.authorized_resolvers.contains(&account_id)
+    }
+
+    pub fn get_taker_address(&self) -> ChainAddress {
+        self.immutables.taker_address.clone()
+    }
+
+    /// Taker EVM address, None if the taker is on Tezos or NEAR
+    pub fn get_taker_evm_address(&self) -> Option<String> {
+        self.immutables.get_normalized_evm_address()
+    }
+
+    pub fn get_token(&self) -> Option<AccountId> {
+        self.immutables.token.clone()
     }
 
     pub fn get_amount(&self) -> U128 {
         self.immutables.amount
     }
 
+    pub fn get_token_balance(&self) -> U128 {
+        self.token_balance
+    }
+
+    /// Check if the escrow holds what is left of its amount (always true for native NEAR escrows)
+    /// Filled parts already left the token balance, so only the remaining amount must be held
+    pub fn is_funded(&self) -> bool {
+        self.immutables.is_native()
+            || self.token_balance.0 >= self.immutables.amount.0 - self.filled_amount.0
+    }
+
+    pub fn get_parts_count(&self) -> Option<u32> {
+        self.immutables.parts_count
+    }
+
+    pub fn get_filled_amount(&self) -> U128 {
+        self.filled_amount
+    }
+
+    pub fn get_filled_parts(&self) -> Vec<u32> {
+        self.filled_parts.clone()
+    }
+
+    /// Amount still locked in the escrow (less than amount only for partially filled escrows)
+    pub fn get_remaining_amount(&self) -> U128 {
+        U128(self.immutables.amount.0 - self.filled_amount.0)
+    }
+
     pub fn get_safety_deposit(&self) -> U128 {
         self.immutables.safety_deposit
     }
@@ -218,20 +1081,37 @@ impl HTLCEscrow {
         self.timelocks.cancellation_timelock
     }
 
+    pub fn get_public_cancellation_timelock(&self) -> u64 {
+        self.timelocks.public_cancellation_timelock
+    }
+
     pub fn get_deposited_amount(&self) -> U128 {
         self.deposited_amount
     }
 
+    /// NEAR attached on creation beyond amount + safety deposit, returned to the depositor once final
+    pub fn get_excess_deposit(&self) -> U128 {
+        U128(
+            self.deposited_amount
+                .0
+                .saturating_sub(self.immutables.get_required_native_deposit()),
+        )
+    }
+
     pub fn get_depositor(&self) -> AccountId {
         self.depositor.clone()
     }
 
+    pub fn get_state(&self) -> EscrowState {
+        self.state
+    }
+
     pub fn is_withdrawn(&self) -> bool {
-        self.is_withdrawn
+        self.state == EscrowState::Withdrawn
     }
 
     pub fn is_cancelled(&self) -> bool {
-        self.is_cancelled
+        self.state == EscrowState::Cancelled
     }
 
     pub fn get_revealed_secret(&self) -> Option<String> {
@@ -242,19 +1122,59 @@ impl HTLCEscrow {
         self.timelocks.created_at
     }
 
-    /// Check if escrow is active (not withdrawn and not cancelled)
+    /// Timelocks packed like TimelocksLib (deployedAt + per-stage offsets)
+    pub fn get_packed_timelocks(&self) -> String {
+        self.timelocks.to_packed()
+    }
+
+    pub fn get_rescue_delay(&self) -> u64 {
+        self.rescue_delay
+    }
+
+    /// Timestamp from which the depositor can rescue funds (TimelocksLib.rescueStart)
+    pub fn get_rescue_start(&self) -> u64 {
+        self.timelocks.rescue_start(self.rescue_delay)
+    }
+
+    /// Check if escrow is active (not withdrawn, cancelled or rescued)
     pub fn is_active(&self) -> bool {
-        !self.is_withdrawn && !self.is_cancelled
+        self.state == EscrowState::Active
     }
 
     /// Get current status as string
     pub fn get_status(&self) -> String {
-        if self.is_withdrawn {
-            "withdrawn".to_string()
-        } else if self.is_cancelled {
-            "cancelled".to_string()
-        } else {
-            "active".to_string()
+        match self.state {
+            EscrowState::Active => "active",
+            EscrowState::Withdrawn => "withdrawn",
+            EscrowState::Cancelled => "cancelled",
+            EscrowState::Rescued => "rescued",
+            EscrowState::Closed => "closed",
+        }
+        .to_string()
+    }
+
+    /// Everything about the escrow in one call: immutables, timelocks, balances and state
+    pub fn get_escrow(&self) -> EscrowView {
+        EscrowView {
+            kind: self.kind,
+            state: self.state,
+            immutables: self.immutables.clone(),
+            immutables_hash: self.get_immutables_hash(),
+            timelocks: self.timelocks.view(self.rescue_delay),
+            balances: BalancesView {
+                deposited_amount: self.deposited_amount,
+                excess_deposit: self.get_excess_deposit(),
+                token_balance: self.token_balance,
+                filled_amount: self.filled_amount,
+                remaining_amount: self.get_remaining_amount(),
+                filled_parts: self.filled_parts.clone(),
+                pending_payouts: self.pending_payouts,
+            },
+            depositor: self.depositor.clone(),
+            taker: self.taker.clone(),
+            authorized_resolvers: self.authorized_resolvers.clone(),
+            token_funder: self.token_funder.clone(),
+            revealed_secret: self.revealed_secret.clone(),
         }
     }
 
@@ -268,11 +1188,16 @@ impl HTLCEscrow {
         self.timelocks.can_withdraw()
     }
 
-    /// Check if cancellation is currently allowed
+    /// Check if private cancellation is currently allowed
     pub fn can_cancel_now(&self) -> bool {
         self.timelocks.can_cancel()
     }
 
+    /// Check if public cancellation is currently allowed
+    pub fn can_public_cancel(&self) -> bool {
+        self.timelocks.can_public_cancel()
+    }
+
     /// Get time remaining until withdrawal
     pub fn time_until_withdrawal(&self) -> Option<u64> {
         self.timelocks.time_until_withdrawal()
@@ -282,4 +1207,485 @@ impl HTLCEscrow {
     pub fn time_until_cancellation(&self) -> Option<u64> {
         self.timelocks.time_until_cancellation()
     }
+
+    /// Get time remaining until public cancellation
+    pub fn time_until_public_cancellation(&self) -> Option<u64> {
+        self.timelocks.time_until_public_cancellation()
+    }
+
+    /// Timestamp from which emergency_refund can be called
+    pub fn get_emergency_timelock(&self) -> u64 {
+        self.timelocks.emergency_timelock()
+    }
+
+    /// Check if emergency refund is currently allowed (and the escrow still holds funds)
+    pub fn can_emergency_refund(&self) -> bool {
+        self.is_active() && self.timelocks.can_emergency_refund()
+    }
+
+    /// Get time remaining until emergency refund
+    pub fn time_until_emergency_refund(&self) -> Option<u64> {
+        self.timelocks.time_until_emergency()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timelocks::EMERGENCY_REFUND_DELAY;
+    use ed25519_dalek::{Signer, SigningKey};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, CurveType};
+
+    const NOW: u64 = 1_700_000_000;
+    const AMOUNT: u128 = 1_000;
+    const SAFETY_DEPOSIT: u128 = 100;
+
+    fn maker() -> AccountId {
+        accounts(0)
+    }
+
+    fn resolver() -> AccountId {
+        accounts(1)
+    }
+
+    fn token() -> AccountId {
+        "usdc.near".parse().unwrap()
+    }
+
+    fn escrow_account() -> AccountId {
+        "escrow.factory.near".parse().unwrap()
+    }
+
+    /// Call the escrow as `predecessor` at `seconds`, attaching `deposit` yoctoNEAR
+    fn set_context(predecessor: AccountId, seconds: u64, deposit: u128) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(escrow_account())
+            .signer_account_id(predecessor.clone())
+            .predecessor_account_id(predecessor)
+            .block_timestamp(seconds * 1_000_000_000)
+            .attached_deposit(NearToken::from_yoctonear(deposit))
+            .build());
+    }
+
+    /// Secret number `index` as 32-byte hex
+    fn secret(index: u8) -> String {
+        hex::encode([index; 32])
+    }
+
+    fn hashlock(index: u8) -> String {
+        hex::encode(env::sha256(&[index; 32]))
+    }
+
+    /// Merkle root of secrets 0 and 1, with the proof of each
+    fn merkle_tree() -> (String, [Vec<String>; 2]) {
+        let leaves = [0u8, 1].map(|index| {
+            merkle::leaf(
+                HashAlgorithm::Sha256,
+                index as u32,
+                &env::sha256(&[index; 32]),
+            )
+        });
+        let mut pair = leaves.clone();
+        pair.sort();
+        let root = env::sha256(&pair.concat());
+        (
+            hex::encode(root),
+            [vec![hex::encode(&leaves[1])], vec![hex::encode(&leaves[0])]],
+        )
+    }
+
+    /// Destination escrow created by the resolver, withdrawable from NOW + 60
+    fn escrow(token: Option<AccountId>, hashlock: String, parts_count: Option<u32>) -> HTLCEscrow {
+        let deposit = match token {
+            Some(_) => SAFETY_DEPOSIT,
+            None => AMOUNT + SAFETY_DEPOSIT,
+        };
+        set_context(resolver(), NOW, deposit);
+        HTLCEscrow::new(
+            hex::encode([7u8; 32]),
+            hashlock,
+            None,
+            parts_count,
+            maker(),
+            None,
+            ChainAddress::Evm(format!("0x{}", "ab".repeat(20))),
+            token,
+            U128(AMOUNT),
+            U128(SAFETY_DEPOSIT),
+            Some(NOW + 60),
+            Some(NOW + 3_600),
+            Some(NOW + 7_200),
+            Some(NOW + 10_800),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Source escrow locked by the maker, the resolver is the taker
+    fn source_escrow() -> HTLCEscrow {
+        set_context(maker(), NOW, AMOUNT + SAFETY_DEPOSIT);
+        HTLCEscrow::new_src(
+            hex::encode([7u8; 32]),
+            hashlock(0),
+            None,
+            None,
+            maker(),
+            None,
+            resolver(),
+            ChainAddress::Evm(format!("0x{}", "ab".repeat(20))),
+            None,
+            U128(AMOUNT),
+            U128(SAFETY_DEPOSIT),
+            Some(NOW + 60),
+            Some(NOW + 3_600),
+            Some(NOW + 7_200),
+            Some(NOW + 10_800),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Token escrow funded with ft_transfer_call by the resolver
+    fn funded_token_escrow(hashlock: String, parts_count: Option<u32>) -> HTLCEscrow {
+        let mut escrow = escrow(Some(token()), hashlock, parts_count);
+        set_context(token(), NOW, 0);
+        escrow.ft_on_transfer(resolver(), U128(AMOUNT), String::new());
+        escrow
+    }
+
+    /// Key the maker signs withdrawal authorizations with
+    fn maker_signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[9u8; 32])
+    }
+
+    /// Destination escrow created by the maker itself with its public key
+    fn authorized_escrow() -> HTLCEscrow {
+        let public_key = PublicKey::from_parts(
+            CurveType::ED25519,
+            maker_signing_key().verifying_key().to_bytes().to_vec(),
+        )
+        .unwrap();
+        set_context(maker(), NOW, AMOUNT + SAFETY_DEPOSIT);
+        HTLCEscrow::new(
+            hex::encode([7u8; 32]),
+            hashlock(0),
+            None,
+            None,
+            maker(),
+            Some(public_key),
+            ChainAddress::Evm(format!("0x{}", "ab".repeat(20))),
+            None,
+            U128(AMOUNT),
+            U128(SAFETY_DEPOSIT),
+            Some(NOW + 60),
+            Some(NOW + 3_600),
+            Some(NOW + 7_200),
+            Some(NOW + 10_800),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Authorization for `escrow_id` paying out to accounts(3), with its hex signature
+    fn authorization(escrow_id: AccountId, nonce: u64) -> (WithdrawalAuthorization, String) {
+        let authorization = WithdrawalAuthorization {
+            escrow_id,
+            recipient: accounts(3),
+            expires_at: NOW + 600,
+            nonce,
+        };
+        let signature = hex::encode(
+            maker_signing_key()
+                .sign(&authorization.message())
+                .to_bytes(),
+        );
+        (authorization, signature)
+    }
+
+    #[test]
+    fn token_escrow_is_filled_in_two_parts() {
+        let (root, proofs) = merkle_tree();
+        let mut escrow = funded_token_escrow(root, Some(2));
+        let [first_proof, second_proof] = proofs;
+
+        set_context(resolver(), NOW + 60, 0);
+        escrow.withdraw_part(0, secret(0), first_proof);
+        assert_eq!(escrow.get_state(), EscrowState::Active);
+        assert!(escrow.is_funded());
+        assert_eq!(escrow.get_token_balance(), U128(AMOUNT / 2));
+
+        escrow.withdraw_part(1, secret(1), second_proof);
+        assert_eq!(escrow.get_state(), EscrowState::Withdrawn);
+        assert_eq!(escrow.get_filled_amount(), U128(AMOUNT));
+        assert_eq!(escrow.get_token_balance(), U128(0));
+        assert_eq!(escrow.pending_payouts, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Escrow not funded")]
+    fn unfunded_token_escrow_cannot_be_withdrawn() {
+        let mut escrow = escrow(Some(token()), hashlock(0), None);
+        set_context(resolver(), NOW + 60, 0);
+        escrow.withdraw(secret(0));
+    }
+
+    #[test]
+    fn failed_rescue_restores_the_escrow() {
+        let mut escrow = funded_token_escrow(hashlock(0), None);
+
+        set_context(resolver(), NOW + DEFAULT_RESCUE_DELAY, 0);
+        escrow.rescue_funds(Some(token()), U128(AMOUNT));
+        assert_eq!(escrow.get_state(), EscrowState::Rescued);
+        assert_eq!(escrow.get_token_balance(), U128(0));
+        assert_eq!(escrow.pending_payouts, 1);
+
+        set_context(escrow_account(), NOW + DEFAULT_RESCUE_DELAY, 0);
+        let rescued = escrow.resolve_rescue(
+            Some(token()),
+            U128(AMOUNT),
+            true,
+            true,
+            Err(PromiseError::Failed),
+        );
+        assert!(!rescued);
+        assert_eq!(escrow.get_state(), EscrowState::Active);
+        assert_eq!(escrow.get_token_balance(), U128(AMOUNT));
+        assert_eq!(escrow.pending_payouts, 0);
+    }
+
+    #[test]
+    fn emergency_refund_emits_its_own_event() {
+        let mut escrow = escrow(None, hashlock(0), None);
+
+        set_context(accounts(2), NOW + 7_200 + EMERGENCY_REFUND_DELAY, 0);
+        escrow.emergency_refund();
+        assert_eq!(escrow.get_state(), EscrowState::Cancelled);
+        let logs = near_sdk::test_utils::get_logs();
+        assert!(logs[0].contains(r#""event":"emergency_refunded""#));
+        assert!(!logs.iter().any(|log| log.contains("escrow_cancelled")));
+    }
+
+    #[test]
+    fn token_funding_emits_an_event() {
+        funded_token_escrow(hashlock(0), None);
+        let logs = near_sdk::test_utils::get_logs();
+        assert!(logs[0].contains(r#""event":"escrow_funded""#));
+        assert!(logs[0].contains(&format!(r#""funder":"{}""#, resolver())));
+    }
+
+    #[test]
+    #[should_panic(expected = "EVM address must be 20 bytes (40 hex characters)")]
+    fn evm_immutables_hash_rejects_short_maker_address() {
+        let escrow = escrow(None, hashlock(0), None);
+        escrow.get_evm_immutables_hash(
+            "0x1234".to_string(),
+            format!("0x{}", "00".repeat(20)),
+            None,
+        );
+    }
+
+    #[test]
+    fn maker_redirects_destination_withdrawal_in_private_period() {
+        let mut escrow = escrow(None, hashlock(0), None);
+
+        set_context(maker(), NOW + 60, 0);
+        escrow.withdraw_to(secret(0), accounts(3));
+        assert_eq!(escrow.get_state(), EscrowState::Withdrawn);
+        let logs = near_sdk::test_utils::get_logs();
+        assert!(logs[0].contains(&format!(r#""recipient":"{}""#, accounts(3))));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the withdrawal recipient can choose another recipient")]
+    fn resolver_cannot_redirect_destination_withdrawal() {
+        let mut escrow = escrow(None, hashlock(0), None);
+
+        set_context(resolver(), NOW + 60, 0);
+        escrow.withdraw_to(secret(0), resolver());
+    }
+
+    #[test]
+    fn failed_withdrawal_payout_is_rolled_back() {
+        let mut escrow = funded_token_escrow(hashlock(0), None);
+
+        set_context(resolver(), NOW + 60, 0);
+        escrow.withdraw(secret(0));
+        assert_eq!(escrow.get_state(), EscrowState::Withdrawn);
+        assert_eq!(escrow.pending_payouts, 1);
+
+        set_context(escrow_account(), NOW + 60, 0);
+        let paid = escrow.resolve_payout(
+            maker(),
+            U128(AMOUNT),
+            PayoutKind::Withdrawal,
+            vec![],
+            Err(PromiseError::Failed),
+        );
+        assert!(matches!(paid, PromiseOrValue::Value(false)));
+        let logs = near_sdk::test_utils::get_logs();
+        assert!(logs[0].contains(r#""kind":"withdrawal""#));
+        assert_eq!(escrow.get_state(), EscrowState::Active);
+        assert_eq!(escrow.get_token_balance(), U128(AMOUNT));
+        assert_eq!(escrow.pending_payouts, 0);
+
+        // The withdrawal can be retried once the maker registered with the token
+        set_context(resolver(), NOW + 60, 0);
+        escrow.withdraw(secret(0));
+        assert_eq!(escrow.get_state(), EscrowState::Withdrawn);
+    }
+
+    #[test]
+    fn failed_part_payout_is_rolled_back() {
+        let (root, proofs) = merkle_tree();
+        let mut escrow = funded_token_escrow(root, Some(2));
+        let [first_proof, second_proof] = proofs;
+
+        set_context(resolver(), NOW + 60, 0);
+        escrow.withdraw_part(0, secret(0), first_proof);
+        escrow.withdraw_part(1, secret(1), second_proof);
+        assert_eq!(escrow.get_state(), EscrowState::Withdrawn);
+
+        set_context(escrow_account(), NOW + 60, 0);
+        escrow.resolve_payout(
+            maker(),
+            U128(AMOUNT / 2),
+            PayoutKind::PartialWithdrawal(1),
+            vec![],
+            Err(PromiseError::Failed),
+        );
+        assert_eq!(escrow.get_state(), EscrowState::Active);
+        assert_eq!(escrow.get_filled_amount(), U128(AMOUNT / 2));
+        assert_eq!(escrow.get_token_balance(), U128(AMOUNT / 2));
+        assert_eq!(escrow.pending_payouts, 1);
+        assert!(escrow.is_funded());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the maker or the escrow factory can set the maker public key")]
+    fn resolver_cannot_set_the_maker_public_key() {
+        set_context(resolver(), NOW, AMOUNT + SAFETY_DEPOSIT);
+        HTLCEscrow::new(
+            hex::encode([7u8; 32]),
+            hashlock(0),
+            None,
+            None,
+            maker(),
+            Some(
+                "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp"
+                    .parse()
+                    .unwrap(),
+            ),
+            ChainAddress::Evm(format!("0x{}", "ab".repeat(20))),
+            None,
+            U128(AMOUNT),
+            U128(SAFETY_DEPOSIT),
+            Some(NOW + 60),
+            Some(NOW + 3_600),
+            Some(NOW + 7_200),
+            Some(NOW + 10_800),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn relayer_withdraws_with_maker_authorization() {
+        let mut escrow = authorized_escrow();
+        let (authorization, signature) = authorization(escrow_account(), 1);
+
+        set_context(accounts(2), NOW + 60, 0);
+        escrow.withdraw_with_authorization(secret(0), authorization, signature);
+        assert_eq!(escrow.get_state(), EscrowState::Withdrawn);
+        let logs = near_sdk::test_utils::get_logs();
+        assert!(logs[0].contains(&format!(r#""recipient":"{}""#, accounts(3))));
+    }
+
+    #[test]
+    #[should_panic(expected = "Authorization expired")]
+    fn expired_authorization_is_rejected() {
+        let mut escrow = authorized_escrow();
+        let (authorization, signature) = authorization(escrow_account(), 1);
+
+        set_context(accounts(2), NOW + 600, 0);
+        escrow.withdraw_with_authorization(secret(0), authorization, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Authorization nonce already used")]
+    fn authorization_nonce_cannot_be_reused() {
+        let mut escrow = authorized_escrow();
+        let (first, first_signature) = authorization(escrow_account(), 1);
+        let (second, second_signature) = authorization(escrow_account(), 1);
+
+        set_context(accounts(2), NOW + 60, 0);
+        escrow.withdraw_with_authorization(secret(0), first, first_signature);
+        escrow.withdraw_with_authorization(secret(0), second, second_signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "Authorization is for another escrow")]
+    fn authorization_for_another_escrow_is_rejected() {
+        let mut escrow = authorized_escrow();
+        let (authorization, signature) = authorization("other.factory.near".parse().unwrap(), 1);
+
+        set_context(accounts(2), NOW + 60, 0);
+        escrow.withdraw_with_authorization(secret(0), authorization, signature);
+    }
+
+    #[test]
+    fn taker_withdraws_source_escrow() {
+        let mut escrow = source_escrow();
+
+        set_context(resolver(), NOW + 60, 0);
+        escrow.withdraw(secret(0));
+        assert_eq!(escrow.get_state(), EscrowState::Withdrawn);
+        let logs = near_sdk::test_utils::get_logs();
+        assert!(logs[0].contains(&format!(r#""recipient":"{}""#, resolver())));
+    }
+
+    #[test]
+    fn taker_cancels_source_escrow_back_to_maker() {
+        let mut escrow = source_escrow();
+
+        set_context(resolver(), NOW + 7_200, 0);
+        escrow.cancel();
+        assert_eq!(escrow.get_state(), EscrowState::Cancelled);
+        let logs = near_sdk::test_utils::get_logs();
+        assert!(logs[0].contains(&format!(r#""recipient":"{}""#, maker())));
+    }
+
+    #[test]
+    #[should_panic(expected = "Withdrawal period is over")]
+    fn source_escrow_withdrawal_closes_at_cancellation() {
+        let mut escrow = source_escrow();
+
+        set_context(resolver(), NOW + 7_200, 0);
+        escrow.withdraw(secret(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Withdrawal period is over")]
+    fn public_withdrawal_closes_at_cancellation() {
+        let mut escrow = source_escrow();
+
+        set_context(accounts(2), NOW + 7_200, 0);
+        escrow.publicwithdraw(secret(0));
+    }
 }
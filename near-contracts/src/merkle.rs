@@ -0,0 +1,58 @@
+// Merkle tree of secrets for partial fills of NEAR HTLC Escrow
+// Same layout as the 1inch MerkleStorageInvalidator: leaves are (index, secret hash),
+// parent nodes hash the sorted pair of children so proofs don't need left/right flags
+// Every node uses the escrow's hash algorithm (keccak256 gives the same root as on EVM)
+use crate::immutables::HashAlgorithm;
+
+/// Compute the leaf for secret number `index`: hash(index as u64 big-endian || secret_hash)
+pub fn leaf(algorithm: HashAlgorithm, index: u32, secret_hash: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(8 + secret_hash.len());
+    data.extend_from_slice(&(index as u64).to_be_bytes());
+    data.extend_from_slice(secret_hash);
+    algorithm.hash(&data)
+}
+
+/// Hash two nodes in sorted order
+fn hash_pair(algorithm: HashAlgorithm, a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(a.len() + b.len());
+    if a <= b {
+        data.extend_from_slice(a);
+        data.extend_from_slice(b);
+    } else {
+        data.extend_from_slice(b);
+        data.extend_from_slice(a);
+    }
+    algorithm.hash(&data)
+}
+
+/// Walk the proof from the leaf up to the root
+/// Returns None if any proof node is not a 32-byte hex string
+pub fn process_proof(algorithm: HashAlgorithm, leaf: Vec<u8>, proof: &[String]) -> Option<Vec<u8>> {
+    proof.iter().try_fold(leaf, |node, sibling| {
+        let sibling = hex::decode(sibling.trim_start_matches("0x")).ok()?;
+        if sibling.len() != 32 {
+            return None;
+        }
+        Some(hash_pair(algorithm, &node, &sibling))
+    })
+}
+
+/// Verify that `leaf` is part of the tree with the given hex encoded root
+pub fn verify(algorithm: HashAlgorithm, root: &str, leaf: Vec<u8>, proof: &[String]) -> bool {
+    match (
+        process_proof(algorithm, leaf, proof),
+        hex::decode(root.trim_start_matches("0x")),
+    ) {
+        (Some(computed), Ok(expected)) => computed == expected,
+        _ => false,
+    }
+}
+
+/// Share of `amount` released by the part at `index` out of `parts`
+/// Shares are taken from a running total so they always add up to exactly `amount`
+pub fn part_amount(amount: u128, index: u32, parts: u32) -> u128 {
+    let parts = parts as u128;
+    // floor(amount * i / parts) without overflowing on large token amounts
+    let cumulative = |i: u32| (amount / parts) * i as u128 + (amount % parts) * i as u128 / parts;
+    cumulative(index + 1) - cumulative(index)
+}
@@ -5,31 +5,106 @@ use near_sdk::{
 };
 use serde::{Deserialize, Serialize};
 
-/// Timelock stages for escrow lifecycle (matching Ethereum destination chain)
+use crate::view::{StageTime, TimelocksView};
+
+/// Delay after the cancellation timelock before emergency refund opens (24 hours, 86400 blocks in block height mode)
+pub const EMERGENCY_REFUND_DELAY: u64 = 86400;
+
+/// Timelock stages for escrow lifecycle (matching Ethereum TimelocksLib.Stage)
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum TimelockStage {
-    Withdrawal,       // Private withdrawal period (only maker with secret)
-    PublicWithdrawal, // Public withdrawal period (anyone with secret)
-    Cancellation,     // Cancellation period (anyone can cancel)
+    Withdrawal,         // Private withdrawal period (only maker with secret)
+    PublicWithdrawal,   // Public withdrawal period (anyone with secret)
+    Cancellation,       // Private cancellation period (only depositor / taker)
+    PublicCancellation, // Public cancellation period (anyone can cancel)
+}
+
+/// Allowed start of a stage, in seconds (blocks in block height mode) after creation
+#[near(serializers = [json])]
+#[derive(Clone, Copy)]
+pub struct StageBounds {
+    pub min: u64,
+    pub max: u64,
+}
+
+/// Bounds the timelocks of an escrow must respect, validated at init
+/// Factories set it per deployment, escrows created without one use the default
+#[near(serializers = [json])]
+#[derive(Clone)]
+pub struct TimelockPolicy {
+    pub withdrawal: StageBounds,
+    pub public_withdrawal: StageBounds,
+    pub cancellation: StageBounds,
+    pub public_cancellation: StageBounds,
+    pub min_stage_gap: u64, // Shortest allowed period between two consecutive stages
+}
+
+impl Default for TimelockPolicy {
+    /// Withdrawal within a day, everything else within 6 days so the default 7-day rescue delay
+    /// always ends after the public cancellation timelock, and at least a minute per stage
+    fn default() -> Self {
+        let within = |max: u64| StageBounds { min: 0, max };
+        Self {
+            withdrawal: within(86400),
+            public_withdrawal: within(86400 * 6),
+            cancellation: within(86400 * 6),
+            public_cancellation: within(86400 * 6),
+            min_stage_gap: 60,
+        }
+    }
+}
+
+/// Clock the timelocks are measured in
+/// Block heights can't be skewed by validators like block timestamps, which suits short destination locks
+/// In block height mode every value (stages, policy bounds, rescue and emergency delays) counts blocks
+#[near(serializers = [json,borsh])]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum TimelockMode {
+    #[default]
+    Timestamp, // Seconds (block_timestamp)
+    BlockHeight, // Blocks (block_height)
 }
 
-/// Timelock configuration and utilities (destination chain pattern)
+impl TimelockMode {
+    /// Current time in this mode's unit
+    pub fn now(&self) -> u64 {
+        match self {
+            TimelockMode::Timestamp => Timelocks::get_current_timestamp(),
+            TimelockMode::BlockHeight => env::block_height(),
+        }
+    }
+
+    /// Unit suffix used in status strings
+    fn unit(&self) -> &'static str {
+        match self {
+            TimelockMode::Timestamp => "s",
+            TimelockMode::BlockHeight => " blocks",
+        }
+    }
+}
+
+/// Timelock configuration and utilities
 #[near(serializers = [json,borsh])]
 pub struct Timelocks {
-    pub withdrawal_timelock: u64, // Timestamp when private withdrawal is allowed
-    pub public_withdrawal_timelock: u64, // Timestamp when public withdrawal is allowed
-    pub cancellation_timelock: u64, // Timestamp when cancellation is allowed
-    pub created_at: u64,          // Contract creation timestamp
+    pub withdrawal_timelock: u64, // Timestamp (or block height) when private withdrawal is allowed
+    pub public_withdrawal_timelock: u64, // Timestamp (or block height) when public withdrawal is allowed
+    pub cancellation_timelock: u64, // Timestamp (or block height) when private cancellation is allowed
+    pub public_cancellation_timelock: u64, // Timestamp (or block height) when public cancellation is allowed
+    pub created_at: u64, // Deployment timestamp (deployedAt in TimelocksLib), stage offsets are relative to it
+    pub mode: TimelockMode,
 }
 
 impl Timelocks {
-    /// Create new timelocks with validation (destination chain pattern)
+    /// Create new timelocks with validation
     pub fn new(
+        mode: TimelockMode,
         withdrawal_timelock: u64,
         public_withdrawal_timelock: u64,
         cancellation_timelock: u64,
+        public_cancellation_timelock: u64,
     ) -> Self {
-        let current_time = Self::get_current_timestamp();
+        let current_time = mode.now();
 
         // Validate timelock sequence
         Self::validate_timelock_sequence(
@@ -37,22 +112,133 @@ impl Timelocks {
             withdrawal_timelock,
             public_withdrawal_timelock,
             cancellation_timelock,
+            public_cancellation_timelock,
         );
 
         Self {
             withdrawal_timelock,
             public_withdrawal_timelock,
             cancellation_timelock,
+            public_cancellation_timelock,
             created_at: current_time,
+            mode,
+        }
+    }
+
+    /// Create timelocks from the packed TimelocksLib value (32-byte hex, same uint256 as on EVM)
+    /// Bits 224..256 hold deployedAt, bits 32*stage hold the offset of each stage in seconds
+    /// deployedAt is always set to the current time, like the EVM factory does on deployment
+    /// (setDeployedAt(block.timestamp)), so a backdated value can't shorten the policy offsets
+    /// In block height mode deployedAt and the offsets are block heights / blocks
+    pub fn from_packed(mode: TimelockMode, packed: &str) -> Self {
+        let bytes = hex::decode(packed.trim_start_matches("0x"))
+            .unwrap_or_else(|_| env::panic_str("Packed timelocks must be a hex string"));
+        require!(bytes.len() <= 32, "Packed timelocks must fit in 32 bytes");
+        let mut word = [0u8; 32];
+        word[32 - bytes.len()..].copy_from_slice(&bytes);
+
+        // 32-bit big-endian value `index` counted from the least significant end
+        let field = |index: usize| {
+            let end = 32 - index * 4;
+            u32::from_be_bytes(word[end - 4..end].try_into().unwrap()) as u64
+        };
+
+        let current_time = mode.now();
+        let deployed_at = current_time;
+
+        let withdrawal_timelock = deployed_at + field(TimelockStage::Withdrawal as usize);
+        let public_withdrawal_timelock =
+            deployed_at + field(TimelockStage::PublicWithdrawal as usize);
+        let cancellation_timelock = deployed_at + field(TimelockStage::Cancellation as usize);
+        let public_cancellation_timelock =
+            deployed_at + field(TimelockStage::PublicCancellation as usize);
+
+        Self::validate_timelock_sequence(
+            current_time,
+            withdrawal_timelock,
+            public_withdrawal_timelock,
+            cancellation_timelock,
+            public_cancellation_timelock,
+        );
+
+        Self {
+            withdrawal_timelock,
+            public_withdrawal_timelock,
+            cancellation_timelock,
+            public_cancellation_timelock,
+            created_at: deployed_at,
+            mode,
+        }
+    }
+
+    /// Create timelocks from escrow init args: either the packed value or all four absolute timestamps
+    pub fn from_init_args(
+        mode: TimelockMode,
+        packed_timelocks: Option<String>,
+        withdrawal_timelock: Option<u64>,
+        public_withdrawal_timelock: Option<u64>,
+        cancellation_timelock: Option<u64>,
+        public_cancellation_timelock: Option<u64>,
+    ) -> Self {
+        match (
+            packed_timelocks,
+            withdrawal_timelock,
+            public_withdrawal_timelock,
+            cancellation_timelock,
+            public_cancellation_timelock,
+        ) {
+            (Some(packed), None, None, None, None) => Self::from_packed(mode, &packed),
+            (
+                None,
+                Some(withdrawal),
+                Some(public_withdrawal),
+                Some(cancellation),
+                Some(public_cancellation),
+            ) => Self::new(
+                mode,
+                withdrawal,
+                public_withdrawal,
+                cancellation,
+                public_cancellation,
+            ),
+            _ => env::panic_str("Pass either packed_timelocks or all four absolute timelocks"),
+        }
+    }
+
+    /// Get the packed TimelocksLib value (32-byte hex with 0x prefix)
+    pub fn to_packed(&self) -> String {
+        let mut word = [0u8; 32];
+        let stages = [
+            (TimelockStage::Withdrawal, self.withdrawal_timelock),
+            (
+                TimelockStage::PublicWithdrawal,
+                self.public_withdrawal_timelock,
+            ),
+            (TimelockStage::Cancellation, self.cancellation_timelock),
+            (
+                TimelockStage::PublicCancellation,
+                self.public_cancellation_timelock,
+            ),
+        ];
+        for (stage, timelock) in stages {
+            let offset = u32::try_from(timelock - self.created_at)
+                .unwrap_or_else(|_| env::panic_str("Timelock offset does not fit in 32 bits"));
+            let end = 32 - stage as usize * 4;
+            word[end - 4..end].copy_from_slice(&offset.to_be_bytes());
         }
+        let deployed_at = u32::try_from(self.created_at)
+            .unwrap_or_else(|_| env::panic_str("Deployment timestamp does not fit in 32 bits"));
+        word[..4].copy_from_slice(&deployed_at.to_be_bytes());
+        format!("0x{}", hex::encode(word))
     }
 
-    /// Validate timelock sequence and timing for destination chain
+    /// Validate timelock sequence and timing
     fn validate_timelock_sequence(
         current_time: u64,
         withdrawal_timelock: u64,
         public_withdrawal_timelock: u64,
         cancellation_timelock: u64,
+        public_cancellation_timelock: u64,
     ) {
         require!(
             withdrawal_timelock > current_time,
@@ -69,44 +255,126 @@ impl Timelocks {
             "Cancellation timelock must be after public withdrawal timelock"
         );
 
-        // Ensure reasonable timelock periods for destination chain
-        let min_withdrawal_delay = 1800; // 30 minutes minimum
-        let max_timelock_period = 86400 * 7; // 7 days maximum for destination
+        require!(
+            public_cancellation_timelock > cancellation_timelock,
+            "Public cancellation timelock must be after cancellation timelock"
+        );
+    }
 
-        // require!(
-        //     withdrawal_timelock >= current_time + min_withdrawal_delay,
-        //     "Withdrawal timelock must be at least 30 minutes in the future"
-        // );
+    /// Check every stage against the policy (offsets from creation and gaps between stages)
+    pub fn require_policy(&self, policy: &TimelockPolicy) {
+        let stages = [
+            (self.withdrawal_timelock, &policy.withdrawal, "Withdrawal"),
+            (
+                self.public_withdrawal_timelock,
+                &policy.public_withdrawal,
+                "Public withdrawal",
+            ),
+            (
+                self.cancellation_timelock,
+                &policy.cancellation,
+                "Cancellation",
+            ),
+            (
+                self.public_cancellation_timelock,
+                &policy.public_cancellation,
+                "Public cancellation",
+            ),
+        ];
+        for (timelock, bounds, name) in stages {
+            let offset = timelock.saturating_sub(self.created_at);
+            require!(
+                offset >= bounds.min,
+                format!(
+                    "{name} timelock must be at least {}s after creation",
+                    bounds.min
+                )
+            );
+            require!(
+                offset <= bounds.max,
+                format!(
+                    "{name} timelock must be at most {}s after creation",
+                    bounds.max
+                )
+            );
+        }
 
-        // require!(
-        //     cancellation_timelock <= current_time + max_timelock_period,
-        //     "Cancellation timelock cannot be more than 7 days in the future"
-        // );
+        for pair in stages.windows(2) {
+            require!(
+                pair[1].0 - pair[0].0 >= policy.min_stage_gap,
+                format!(
+                    "{} timelock must be at least {}s after the {} timelock",
+                    pair[1].2,
+                    policy.min_stage_gap,
+                    pair[0].2.to_lowercase()
+                )
+            );
+        }
     }
 
     /// Check if withdrawal is allowed at current time (private period)
     pub fn can_withdraw(&self) -> bool {
-        let current_time = Self::get_current_timestamp();
+        let current_time = self.now();
         current_time >= self.withdrawal_timelock
     }
 
     /// Check if public withdrawal is allowed at current time
     pub fn can_public_withdraw(&self) -> bool {
-        let current_time = Self::get_current_timestamp();
+        let current_time = self.now();
         current_time >= self.public_withdrawal_timelock
     }
 
-    /// Check if cancellation is allowed at current time
+    /// Check if private cancellation is allowed at current time
     pub fn can_cancel(&self) -> bool {
-        let current_time = Self::get_current_timestamp();
+        let current_time = self.now();
         current_time >= self.cancellation_timelock
     }
 
+    /// Check if public cancellation is allowed at current time
+    pub fn can_public_cancel(&self) -> bool {
+        let current_time = self.now();
+        current_time >= self.public_cancellation_timelock
+    }
+
+    /// Get the timestamp when emergency refund is allowed
+    pub fn emergency_timelock(&self) -> u64 {
+        self.cancellation_timelock + EMERGENCY_REFUND_DELAY
+    }
+
     /// Check if emergency refund is allowed (24 hours after cancellation)
     pub fn can_emergency_refund(&self) -> bool {
-        let current_time = Self::get_current_timestamp();
-        let emergency_timelock = self.cancellation_timelock + 86400; // +24 hours
-        current_time >= emergency_timelock
+        let current_time = self.now();
+        current_time >= self.emergency_timelock()
+    }
+
+    /// Get the start of the rescue period (creation time + rescue delay)
+    pub fn rescue_start(&self, rescue_delay: u64) -> u64 {
+        self.created_at + rescue_delay
+    }
+
+    /// Check if funds can be rescued at current time
+    pub fn can_rescue(&self, rescue_delay: u64) -> bool {
+        let current_time = self.now();
+        current_time >= self.rescue_start(rescue_delay)
+    }
+
+    /// Absolute and remaining times of every stage (get_escrow view)
+    pub fn view(&self, rescue_delay: u64) -> TimelocksView {
+        let stage = |starts_at: u64| StageTime {
+            starts_at,
+            remaining: starts_at.checked_sub(self.now()).filter(|left| *left > 0),
+        };
+        TimelocksView {
+            mode: self.mode,
+            created_at: self.created_at,
+            packed: self.to_packed(),
+            withdrawal: stage(self.withdrawal_timelock),
+            public_withdrawal: stage(self.public_withdrawal_timelock),
+            cancellation: stage(self.cancellation_timelock),
+            public_cancellation: stage(self.public_cancellation_timelock),
+            emergency_refund: stage(self.emergency_timelock()),
+            rescue: stage(self.rescue_start(rescue_delay)),
+        }
     }
 
     /// Get current timestamp in seconds
@@ -114,9 +382,14 @@ impl Timelocks {
         env::block_timestamp_ms() / 1000
     }
 
+    /// Current time in the unit of these timelocks (seconds or block height)
+    pub fn now(&self) -> u64 {
+        self.mode.now()
+    }
+
     /// Get time remaining until withdrawal is allowed
     pub fn time_until_withdrawal(&self) -> Option<u64> {
-        let current_time = Self::get_current_timestamp();
+        let current_time = self.now();
         if current_time >= self.withdrawal_timelock {
             None
         } else {
@@ -126,7 +399,7 @@ impl Timelocks {
 
     /// Get time remaining until public withdrawal is allowed
     pub fn time_until_public_withdrawal(&self) -> Option<u64> {
-        let current_time = Self::get_current_timestamp();
+        let current_time = self.now();
         if current_time >= self.public_withdrawal_timelock {
             None
         } else {
@@ -136,7 +409,7 @@ impl Timelocks {
 
     /// Get time remaining until cancellation is allowed
     pub fn time_until_cancellation(&self) -> Option<u64> {
-        let current_time = Self::get_current_timestamp();
+        let current_time = self.now();
         if current_time >= self.cancellation_timelock {
             None
         } else {
@@ -144,10 +417,20 @@ impl Timelocks {
         }
     }
 
+    /// Get time remaining until public cancellation is allowed
+    pub fn time_until_public_cancellation(&self) -> Option<u64> {
+        let current_time = self.now();
+        if current_time >= self.public_cancellation_timelock {
+            None
+        } else {
+            Some(self.public_cancellation_timelock - current_time)
+        }
+    }
+
     /// Get time remaining until emergency refund is allowed
     pub fn time_until_emergency(&self) -> Option<u64> {
-        let current_time = Self::get_current_timestamp();
-        let emergency_timelock = self.cancellation_timelock + 86400;
+        let current_time = self.now();
+        let emergency_timelock = self.emergency_timelock();
         if current_time >= emergency_timelock {
             None
         } else {
@@ -179,6 +462,13 @@ impl Timelocks {
                     Err("Cancellation timelock not met")
                 }
             }
+            TimelockStage::PublicCancellation => {
+                if self.can_public_cancel() {
+                    Ok(())
+                } else {
+                    Err("Public cancellation timelock not met")
+                }
+            }
         }
     }
 
@@ -192,19 +482,22 @@ impl Timelocks {
 
     /// Get human-readable status of timelocks
     pub fn get_status(&self) -> String {
-        let current_time = Self::get_current_timestamp();
+        let current_time = self.now();
 
         if current_time < self.withdrawal_timelock {
             format!(
-                "Waiting for withdrawal timelock ({}s remaining)",
-                self.withdrawal_timelock - current_time
+                "Waiting for withdrawal timelock ({}{} remaining)",
+                self.withdrawal_timelock - current_time,
+                self.mode.unit()
             )
         } else if current_time < self.public_withdrawal_timelock {
             "Private withdrawal period active".to_string()
         } else if current_time < self.cancellation_timelock {
             "Public withdrawal period active".to_string()
-        } else {
+        } else if current_time < self.public_cancellation_timelock {
             "Cancellation period active".to_string()
+        } else {
+            "Public cancellation period active".to_string()
         }
     }
 
@@ -213,21 +506,115 @@ impl Timelocks {
         withdrawal_delay_seconds: u64,
         public_withdrawal_delay_seconds: u64,
         cancellation_delay_seconds: u64,
+        public_cancellation_delay_seconds: u64,
     ) -> Self {
         let current_time = Self::get_current_timestamp();
         Self::new(
+            TimelockMode::Timestamp,
             current_time + withdrawal_delay_seconds,
             current_time + public_withdrawal_delay_seconds,
             current_time + cancellation_delay_seconds,
+            current_time + public_cancellation_delay_seconds,
         )
     }
 
     /// Create timelocks optimized for NEAR as destination chain in EVM->NEAR swaps
     pub fn create_destination_chain() -> Self {
-        Self::create_with_delays(18, 54, 144) // 30 min, 1.5 hours, 4 hours
+        Self::create_with_delays(18, 54, 144, 216) // 30 min, 1.5 hours, 4 hours, 6 hours
     }
 
     pub fn create_standard() -> Self {
-        Self::create_with_delays(36, 72, 864) // 1 hour, 2 hours, 24 hours
+        Self::create_with_delays(36, 72, 864, 1296) // 1 hour, 2 hours, 24 hours, 36 hours
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn set_time(seconds: u64) {
+        testing_env!(VMContextBuilder::new()
+            .block_timestamp(seconds * 1_000_000_000)
+            .build());
+    }
+
+    fn timelocks() -> Timelocks {
+        Timelocks::new(
+            TimelockMode::Timestamp,
+            NOW + 60,
+            NOW + 3_600,
+            NOW + 7_200,
+            NOW + 10_800,
+        )
+    }
+
+    fn assert_same(left: &Timelocks, right: &Timelocks) {
+        assert_eq!(left.withdrawal_timelock, right.withdrawal_timelock);
+        assert_eq!(
+            left.public_withdrawal_timelock,
+            right.public_withdrawal_timelock
+        );
+        assert_eq!(left.cancellation_timelock, right.cancellation_timelock);
+        assert_eq!(
+            left.public_cancellation_timelock,
+            right.public_cancellation_timelock
+        );
+        assert_eq!(left.created_at, right.created_at);
+    }
+
+    #[test]
+    fn packed_timelocks_round_trip() {
+        set_time(NOW);
+        let timelocks = timelocks();
+        let packed = timelocks.to_packed();
+
+        let unpacked = Timelocks::from_packed(TimelockMode::Timestamp, &packed);
+        assert_same(&unpacked, &timelocks);
+        assert_eq!(unpacked.to_packed(), packed);
+    }
+
+    #[test]
+    fn packed_layout_matches_timelocks_lib() {
+        set_time(NOW);
+        let packed = timelocks().to_packed();
+        let word = hex::decode(packed.trim_start_matches("0x")).unwrap();
+        let field = |index: usize| {
+            u32::from_be_bytes(word[28 - index * 4..32 - index * 4].try_into().unwrap())
+        };
+
+        assert_eq!(field(7) as u64, NOW);
+        assert_eq!(field(TimelockStage::Withdrawal as usize), 60);
+        assert_eq!(field(TimelockStage::PublicWithdrawal as usize), 3_600);
+        assert_eq!(field(TimelockStage::Cancellation as usize), 7_200);
+        assert_eq!(field(TimelockStage::PublicCancellation as usize), 10_800);
+    }
+
+    #[test]
+    fn zero_deployed_at_is_set_to_now() {
+        set_time(NOW);
+        let offsets = format!("0x{:08x}{:08x}{:08x}{:08x}", 10_800, 7_200, 3_600, 60);
+        let unpacked = Timelocks::from_packed(TimelockMode::Timestamp, &offsets);
+        assert_same(&unpacked, &timelocks());
+    }
+
+    #[test]
+    fn backdated_deployed_at_is_ignored() {
+        set_time(NOW);
+        let backdated = format!(
+            "0x{:08x}{}{:08x}{:08x}{:08x}{:08x}",
+            NOW - 600,
+            "0".repeat(24),
+            10_800,
+            7_200,
+            3_600,
+            60
+        );
+        let unpacked = Timelocks::from_packed(TimelockMode::Timestamp, &backdated);
+        assert_eq!(unpacked.created_at, NOW);
+        assert_same(&unpacked, &timelocks());
     }
 }
@@ -0,0 +1,36 @@
+// NEP-141 fungible token support for NEAR HTLC Escrow
+use near_sdk::json_types::U128;
+use near_sdk::{ext_contract, near, AccountId, Gas, NearToken};
+
+/// ft_transfer requires exactly 1 yoctoNEAR attached
+pub const ONE_YOCTO: NearToken = NearToken::from_yoctonear(1);
+pub const GAS_FOR_FT_TRANSFER: Gas = Gas::from_tgas(10);
+pub const GAS_FOR_RESOLVE_NATIVE_PAYOUTS: Gas = Gas::from_tgas(5);
+pub const GAS_FOR_RESOLVE_RESCUE: Gas = Gas::from_tgas(5);
+// Also schedules the NEAR side payouts and their resolve_native_payouts callback
+pub const GAS_FOR_RESOLVE_PAYOUT: Gas = Gas::from_tgas(20);
+
+/// Interface of the NEP-141 token contract we pay out from
+#[allow(dead_code)]
+#[ext_contract(ext_ft)]
+pub trait FungibleToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+/// NEAR transfer that is part of a payout (safety deposit to the caller, excess deposit refund)
+#[near(serializers = [json])]
+#[derive(Clone)]
+pub struct NativePayout {
+    pub receiver_id: AccountId,
+    pub amount: U128,
+}
+
+/// Which escrow action a payout belongs to (used to roll back on failure)
+#[near(serializers = [json])]
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PayoutKind {
+    Withdrawal,
+    PartialWithdrawal(u32), // index of the Merkle secret used
+    Cancellation,
+}
@@ -0,0 +1,55 @@
+// Full-state views for NEAR HTLC Escrow
+// Everything a client needs to reconstruct an escrow in a single call
+use near_sdk::json_types::U128;
+use near_sdk::{near, AccountId};
+
+use crate::{EscrowImmutables, EscrowKind, EscrowState, TimelockMode};
+
+/// Start of a stage and the seconds (blocks in block height mode) left until it (None once it has started)
+#[near(serializers = [json])]
+pub struct StageTime {
+    pub starts_at: u64,
+    pub remaining: Option<u64>,
+}
+
+/// Absolute and remaining times of every stage
+#[near(serializers = [json])]
+pub struct TimelocksView {
+    pub mode: TimelockMode,
+    pub created_at: u64,
+    pub packed: String, // TimelocksLib uint256 as hex
+    pub withdrawal: StageTime,
+    pub public_withdrawal: StageTime,
+    pub cancellation: StageTime,
+    pub public_cancellation: StageTime,
+    pub emergency_refund: StageTime,
+    pub rescue: StageTime,
+}
+
+/// Balances held and paid out by the escrow
+#[near(serializers = [json])]
+pub struct BalancesView {
+    pub deposited_amount: U128, // NEAR attached on creation
+    pub excess_deposit: U128,   // Part of it returned to the depositor once final
+    pub token_balance: U128,    // NEP-141 escrows only
+    pub filled_amount: U128,    // Released by partial fills
+    pub remaining_amount: U128, // Still locked
+    pub filled_parts: Vec<u32>,
+    pub pending_payouts: u32,
+}
+
+/// Result of get_escrow
+#[near(serializers = [json])]
+pub struct EscrowView {
+    pub kind: EscrowKind,
+    pub state: EscrowState,
+    pub immutables: EscrowImmutables,
+    pub immutables_hash: String,
+    pub timelocks: TimelocksView,
+    pub balances: BalancesView,
+    pub depositor: AccountId,
+    pub taker: Option<AccountId>,
+    pub authorized_resolvers: Vec<AccountId>,
+    pub token_funder: Option<AccountId>,
+    pub revealed_secret: Option<String>,
+}
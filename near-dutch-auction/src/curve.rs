@@ -0,0 +1,343 @@
+// Auction curves: how the price moves from start price to end price over the auction window
+// Piecewise intermediate points work like the (delay, rate bump) points of 1inch Fusion auctions:
+// the price can decay faster early on than on the straight line from start to end price
+use near_sdk::json_types::U128;
+use near_sdk::{near, require};
+
+use crate::math::{mul_div, pow_wad, U256, WAD};
+
+/// Basis points in 100%
+const BPS: u32 = 10_000;
+
+/// Max intermediate points per auction, bounds storage and gas
+pub const MAX_PRICE_POINTS: usize = 16;
+
+/// Intermediate point of an auction curve
+#[near(serializers = [json, borsh])]
+#[derive(Clone)]
+pub struct PricePoint {
+    /// Offset from the auction start, in ms
+    pub delay: u64,
+    pub price: U128,
+}
+
+/// Price curve of an auction, the price never goes below the end price
+#[near(serializers = [json, borsh])]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[derive(Clone, Default)]
+pub enum AuctionCurve {
+    /// Straight line from start price to end price
+    #[default]
+    Linear,
+    /// Linear between start, the intermediate points and end
+    Piecewise { points: Vec<PricePoint> },
+    /// Price loses `decay_bps` basis points of its value every whole second, floored at end price
+    Exponential { decay_bps: u32 },
+    /// Price drops by an equal share every `interval` ms and reaches end price on the last step
+    Step { interval: u64 },
+}
+
+impl AuctionCurve {
+    /// Checks the curve parameters against the auction window
+    pub fn validate(&self, start_time: u64, end_time: u64, start_price: u128, end_price: u128) {
+        match self {
+            AuctionCurve::Linear => {}
+            AuctionCurve::Piecewise { points } => {
+                validate_points(start_time, end_time, start_price, end_price, points)
+            }
+            AuctionCurve::Exponential { decay_bps } => require!(
+                *decay_bps > 0 && *decay_bps < BPS,
+                "Decay must be between 0 and 10000 basis points"
+            ),
+            AuctionCurve::Step { interval } => require!(
+                *interval > 0 && *interval <= end_time.saturating_sub(start_time),
+                "Step interval must be inside the auction window"
+            ),
+        }
+    }
+
+    /// Price at `now` (ms), start price up to the start and end price from the end on
+    pub fn price_at(
+        &self,
+        start_time: u64,
+        end_time: u64,
+        start_price: u128,
+        end_price: u128,
+        now: u64,
+    ) -> u128 {
+        if now <= start_time {
+            return start_price;
+        }
+        if now >= end_time {
+            return end_price;
+        }
+
+        let elapsed = now - start_time;
+        match self {
+            AuctionCurve::Linear => {
+                piecewise_price(start_time, end_time, start_price, end_price, &[], now)
+            }
+            AuctionCurve::Piecewise { points } => {
+                piecewise_price(start_time, end_time, start_price, end_price, points, now)
+            }
+            AuctionCurve::Exponential { decay_bps } => {
+                // factor = (1 - decay)^seconds in 18-decimal fixed point
+                let base = mul_div(WAD, (BPS - decay_bps) as u128, BPS as u128, false);
+                let factor = pow_wad(base, elapsed / 1000);
+                std::cmp::max(end_price, mul_div(start_price, factor, WAD, false))
+            }
+            AuctionCurve::Step { interval } => {
+                let steps = ((end_time - start_time) / interval) as u128;
+                let step = (elapsed / interval) as u128;
+                if step >= steps || start_price <= end_price {
+                    return end_price;
+                }
+                // Round the drop down so the price stays in the maker's favour
+                start_price - mul_div(start_price - end_price, step, steps, false)
+            }
+        }
+    }
+}
+
+/// Checks the points lie strictly inside the auction window in increasing order
+/// and that the price never goes up from start price through the points to end price
+fn validate_points(
+    start_time: u64,
+    end_time: u64,
+    start_price: u128,
+    end_price: u128,
+    points: &[PricePoint],
+) {
+    require!(points.len() <= MAX_PRICE_POINTS, "Too many price points");
+    let span = end_time.saturating_sub(start_time);
+
+    let mut prev_delay = 0;
+    let mut prev_price = start_price;
+    for point in points {
+        require!(
+            point.delay > prev_delay,
+            "Price point delays must be increasing"
+        );
+        require!(
+            point.delay < span,
+            "Price points must be inside the auction window"
+        );
+        require!(
+            point.price.0 <= prev_price,
+            "Price must not increase over the auction"
+        );
+        prev_delay = point.delay;
+        prev_price = point.price.0;
+    }
+    require!(
+        end_price <= prev_price,
+        "Price must not increase over the auction"
+    );
+}
+
+/// Price at `now` on the curve through the start, the points and the end
+/// Linear between neighbouring points, no points is the plain two-point auction
+fn piecewise_price(
+    start_time: u64,
+    end_time: u64,
+    start_price: u128,
+    end_price: u128,
+    points: &[PricePoint],
+    now: u64,
+) -> u128 {
+    if now <= start_time {
+        return start_price;
+    }
+    if now >= end_time {
+        return end_price;
+    }
+
+    let mut from = (start_time, start_price);
+    let knots = points
+        .iter()
+        .map(|point| (start_time.saturating_add(point.delay), point.price.0))
+        .chain(std::iter::once((end_time, end_price)));
+    for to in knots {
+        if now < to.0 {
+            return interpolate(from, to, now);
+        }
+        from = to;
+    }
+    end_price
+}
+
+/// Linear interpolation, same logic as the EVM implementation in the Limit Order protocol
+/// price = (from_price * (to_time - now) + to_price * (now - from_time)) / span
+fn interpolate(
+    (from_time, from_price): (u64, u128),
+    (to_time, to_price): (u64, u128),
+    now: u64,
+) -> u128 {
+    let span = to_time - from_time;
+    let price = (U256::from(from_price) * U256::from(to_time - now)
+        + U256::from(to_price) * U256::from(now - from_time))
+        / U256::from(span);
+    // A weighted average of two u128 prices fits in a u128
+    price.as_u128()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: u64 = 1_000;
+    const END: u64 = 11_000;
+
+    fn price(curve: &AuctionCurve, now: u64) -> u128 {
+        curve.price_at(START, END, 1_000, 500, now)
+    }
+
+    fn point(delay: u64, price: u128) -> PricePoint {
+        PricePoint {
+            delay,
+            price: U128(price),
+        }
+    }
+
+    #[test]
+    fn every_curve_is_flat_outside_the_window() {
+        let curves = [
+            AuctionCurve::Linear,
+            AuctionCurve::Piecewise {
+                points: vec![point(2_000, 600)],
+            },
+            AuctionCurve::Exponential { decay_bps: 100 },
+            AuctionCurve::Step { interval: 2_500 },
+        ];
+        for curve in curves {
+            assert_eq!(price(&curve, 0), 1_000);
+            assert_eq!(price(&curve, START), 1_000);
+            assert_eq!(price(&curve, END), 500);
+            assert_eq!(price(&curve, u64::MAX), 500);
+        }
+    }
+
+    #[test]
+    fn linear_interpolates() {
+        assert_eq!(price(&AuctionCurve::Linear, START + 1), 999);
+        assert_eq!(price(&AuctionCurve::Linear, 6_000), 750);
+        assert_eq!(price(&AuctionCurve::Linear, END - 1), 500);
+    }
+
+    #[test]
+    fn linear_handles_u128_prices() {
+        let curve = AuctionCurve::Linear;
+        assert_eq!(
+            curve.price_at(0, 2, u128::MAX, u128::MAX - 2, 1),
+            u128::MAX - 1
+        );
+    }
+
+    #[test]
+    fn piecewise_follows_points() {
+        let curve = AuctionCurve::Piecewise {
+            points: vec![point(2_000, 600), point(6_000, 550)],
+        };
+        assert_eq!(price(&curve, START + 1_000), 800);
+        assert_eq!(price(&curve, START + 2_000), 600);
+        assert_eq!(price(&curve, START + 4_000), 575);
+        assert_eq!(price(&curve, START + 6_000), 550);
+        assert_eq!(price(&curve, START + 8_000), 525);
+    }
+
+    #[test]
+    fn piecewise_without_points_is_linear() {
+        let curve = AuctionCurve::Piecewise { points: vec![] };
+        for now in [START + 1, 3_000, 6_000, END - 1] {
+            assert_eq!(price(&curve, now), price(&AuctionCurve::Linear, now));
+        }
+    }
+
+    #[test]
+    fn exponential_decays_per_whole_second() {
+        let curve = AuctionCurve::Exponential { decay_bps: 1_000 };
+        assert_eq!(price(&curve, START + 999), 1_000);
+        assert_eq!(price(&curve, START + 1_000), 900);
+        assert_eq!(price(&curve, START + 2_000), 810);
+        // 1000 * 0.9^7 = 478 is below the end price
+        assert_eq!(price(&curve, START + 7_000), 500);
+    }
+
+    #[test]
+    fn exponential_handles_u128_prices() {
+        let curve = AuctionCurve::Exponential { decay_bps: 5_000 };
+        assert_eq!(
+            curve.price_at(0, 10_000, u128::MAX, 0, 1_000),
+            u128::MAX / 2
+        );
+    }
+
+    #[test]
+    fn step_drops_on_each_interval() {
+        let curve = AuctionCurve::Step { interval: 2_500 };
+        assert_eq!(price(&curve, START + 2_499), 1_000);
+        assert_eq!(price(&curve, START + 2_500), 875);
+        assert_eq!(price(&curve, START + 5_000), 750);
+        assert_eq!(price(&curve, START + 7_500), 625);
+        assert_eq!(price(&curve, END - 1), 625);
+    }
+
+    #[test]
+    fn step_reaches_end_price_on_the_last_full_interval() {
+        // 10s window in 3s steps: drops at 3s, 6s and end price from 9s
+        let curve = AuctionCurve::Step { interval: 3_000 };
+        assert_eq!(price(&curve, START + 3_000), 834);
+        assert_eq!(price(&curve, START + 6_000), 667);
+        assert_eq!(price(&curve, START + 9_000), 500);
+    }
+
+    #[test]
+    fn valid_curves_pass() {
+        AuctionCurve::Linear.validate(START, END, 1_000, 500);
+        AuctionCurve::Piecewise {
+            points: vec![point(1, 1_000), point(9_999, 500)],
+        }
+        .validate(START, END, 1_000, 500);
+        AuctionCurve::Exponential { decay_bps: 9_999 }.validate(START, END, 1_000, 500);
+        AuctionCurve::Step { interval: 10_000 }.validate(START, END, 1_000, 500);
+    }
+
+    #[test]
+    #[should_panic(expected = "Price must not increase over the auction")]
+    fn piecewise_rejects_rising_points() {
+        AuctionCurve::Piecewise {
+            points: vec![point(2_000, 600), point(4_000, 700)],
+        }
+        .validate(START, END, 1_000, 500);
+    }
+
+    #[test]
+    #[should_panic(expected = "Price point delays must be increasing")]
+    fn piecewise_rejects_unordered_points() {
+        AuctionCurve::Piecewise {
+            points: vec![point(4_000, 700), point(2_000, 600)],
+        }
+        .validate(START, END, 1_000, 500);
+    }
+
+    #[test]
+    #[should_panic(expected = "Price points must be inside the auction window")]
+    fn piecewise_rejects_points_at_the_end() {
+        AuctionCurve::Piecewise {
+            points: vec![point(10_000, 500)],
+        }
+        .validate(START, END, 1_000, 500);
+    }
+
+    #[test]
+    #[should_panic(expected = "Decay must be between 0 and 10000 basis points")]
+    fn exponential_rejects_full_decay() {
+        AuctionCurve::Exponential { decay_bps: 10_000 }.validate(START, END, 1_000, 500);
+    }
+
+    #[test]
+    #[should_panic(expected = "Step interval must be inside the auction window")]
+    fn step_rejects_intervals_longer_than_the_window() {
+        AuctionCurve::Step { interval: 10_001 }.validate(START, END, 1_000, 500);
+    }
+}
@@ -5,13 +5,23 @@ use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::U128;
 use near_sdk::require;
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, near, AccountId, PanicOnDefault};
+use near_sdk::store::{LookupMap, LookupSet};
+use near_sdk::{env, near, AccountId, CurveType, NearToken, PanicOnDefault, Promise, PublicKey};
 
+mod curve;
+mod math;
 mod order;
+mod signature;
 
-pub use order::Order;
+pub use curve::{AuctionCurve, PricePoint};
+use math::mul_div;
+pub use order::{AuctionOrder, Order};
+pub use signature::OrderAuthorization;
+
+const NEAR_PER_STORAGE: NearToken = NearToken::from_yoctonear(10u128.pow(19)); // 10e19yⓃ
 
 /// Response with price and order information
+/// `current_price` is the taking amount for the order's full `making_amount`
 #[near(serializers = [json,Borsh])]
 
 pub struct PriceInfo {
@@ -33,12 +43,20 @@ pub struct FilledOrderInfo {
     pub order_hash: String,
     /// Account that created the auction
     pub maker: AccountId,
+    /// Key the maker signed the order with
+    pub maker_public_key: PublicKey,
+    /// Maker's hex ed25519 signature of the order, see `get_order_signing_hash`
+    pub maker_signature: String,
     /// Account that filled the order
     pub taker: AccountId,
     /// Asset being sold
     pub maker_asset: String,
-    /// Amount of asset being sold
+    /// Amount of asset sold in this fill
     pub making_amount: U128,
+    /// Amount the taker owes for this fill at `fill_price`
+    pub taking_amount: U128,
+    /// Making amount left on the order after this fill
+    pub remaining_amount: U128,
     /// Price at which the order was filled
     pub fill_price: U128,
     /// Timestamp when the order was filled
@@ -47,67 +65,195 @@ pub struct FilledOrderInfo {
 
 #[near(contract_state)]
 #[derive(PanicOnDefault)]
-pub struct DutchAuctionCalculator {}
+pub struct DutchAuctionCalculator {
+    /// Registered orders keyed by `Order::hash_order`
+    orders: LookupMap<String, AuctionOrder>,
+    /// Keys makers registered for signing orders
+    maker_keys: LookupSet<(AccountId, PublicKey)>,
+}
+
+/// Calculator state before orders were stored on-chain (no fields)
+#[near(serializers = [borsh])]
+struct OldDutchAuctionCalculator {}
 
 #[near]
 impl DutchAuctionCalculator {
     #[init]
     pub fn new() -> Self {
-        Self {}
+        Self {
+            orders: LookupMap::new(b"o"),
+            maker_keys: LookupSet::new(b"k"),
+        }
     }
 
-    pub fn calc_price(
+    /// Upgrade a calculator deployed before orders and maker keys were stored
+    /// Call it right after deploying the new code
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let _old: OldDutchAuctionCalculator =
+            env::state_read().unwrap_or_else(|| env::panic_str("Calculator state not found"));
+        Self::new()
+    }
+
+    /// Register a key the caller signs orders with
+    /// Only needed for keys that don't also send the `register_order` transaction
+    /// The attached deposit covers the storage, the excess is refunded
+    #[payable]
+    pub fn add_maker_key(&mut self, public_key: PublicKey) {
+        require!(
+            public_key.curve_type() == CurveType::ED25519,
+            "Only ed25519 keys are supported"
+        );
+        let initial_storage = env::storage_usage();
+        let added = self
+            .maker_keys
+            .insert((env::predecessor_account_id(), public_key));
+        require!(added, "Key already registered");
+        self.charge_storage(initial_storage);
+    }
+
+    /// Remove a registered key, orders it already signed stay registered
+    pub fn remove_maker_key(&mut self, public_key: PublicKey) {
+        let removed = self
+            .maker_keys
+            .remove(&(env::predecessor_account_id(), public_key));
+        require!(removed, "Key not registered");
+    }
+
+    /// Whether `maker` registered `public_key` for signing orders
+    pub fn is_maker_key(&self, maker: AccountId, public_key: PublicKey) -> bool {
+        self.maker_keys.contains(&(maker, public_key))
+    }
+
+    /// Hex hash the maker signs to authorize an order with these auction parameters
+    pub fn get_order_signing_hash(
         &self,
+        order: Order,
         start_time: u64,
         end_time: u64,
         start_price: U128,
         end_price: U128,
-    ) -> U128 {
-        let current_time = env::block_timestamp() / 1_000_000; // in ms
+        curve: Option<AuctionCurve>,
+    ) -> String {
+        let hash = OrderAuthorization {
+            auction_contract: &env::current_account_id(),
+            order: &order,
+            start_time,
+            end_time,
+            start_price,
+            end_price,
+            curve: &curve.unwrap_or_default(),
+        }
+        .hash();
+        hex::encode(hash)
+    }
+
+    /// Register an order with its auction parameters, signed by the maker
+    /// Anyone can submit it, `signature` is the maker's hex ed25519 signature of
+    /// `get_order_signing_hash` with `maker_public_key`, which must be the access key
+    /// signing this transaction on the maker account or a key added with `add_maker_key`
+    /// The attached deposit covers the storage, the excess is refunded
+    /// `curve` shapes the price decay, linear by default
+    /// Returns the order hash used by `get_price_info` and `fill_order`
+    #[payable]
+    pub fn register_order(
+        &mut self,
+        order: Order,
+        start_time: u64,
+        end_time: u64,
+        start_price: U128,
+        end_price: U128,
+        curve: Option<AuctionCurve>,
+        maker_public_key: PublicKey,
+        signature: String,
+    ) -> String {
+        require!(order.making_amount.0 > 0, "Making amount must be positive");
+        require!(end_time > start_time, "End time must be after start time");
+        require!(
+            start_price.0 >= end_price.0,
+            "Start price must not be below end price"
+        );
+        let curve = curve.unwrap_or_default();
+        curve.validate(start_time, end_time, start_price.0, end_price.0);
 
-        // clamp now between start_time and end_time
-        let now_clamped = std::cmp::max(
-            start_time as u128,
-            std::cmp::min(end_time as u128, current_time as u128),
+        let order_hash = order.hash_order();
+        require!(
+            !self.orders.contains_key(&order_hash),
+            "Order already registered"
         );
 
-        if now_clamped <= start_time as u128 {
-            return start_price;
-        }
-        if now_clamped >= end_time as u128 {
-            return end_price;
-        }
+        let auction = AuctionOrder {
+            order,
+            maker_public_key,
+            signature,
+            start_time,
+            end_time,
+            start_price,
+            end_price,
+            curve,
+            filled_amount: U128(0),
+        };
+        require!(
+            auction
+                .authorization(&env::current_account_id())
+                .verify(&auction.maker_public_key, &auction.signature),
+            "Invalid maker signature"
+        );
+        require!(
+            self.is_maker_access_key(&auction.order.maker, &auction.maker_public_key),
+            "Public key is not a key of the maker"
+        );
+
+        let initial_storage = env::storage_usage();
+        self.orders.insert(order_hash.clone(), auction);
+        self.orders.flush();
+        self.charge_storage(initial_storage);
 
-        let span = end_time as u128 - start_time as u128;
-        let start_price_u128: u128 = start_price.into();
-        let end_price_u128: u128 = end_price.into();
+        order_hash
+    }
 
-        // Linear interpolation:
-        // same logic as the EVM implementation in the Limit Order protocol.
-        // price = (start_price * (end_time - now) + end_price * (now - start_time)) / span
-        let price = (start_price_u128 * (end_time as u128 - now_clamped)
-            + end_price_u128 * (now_clamped - start_time as u128))
-            / span;
+    /// Get a registered order with its auction parameters
+    pub fn get_order(&self, order_hash: String) -> Option<&AuctionOrder> {
+        self.orders.get(&order_hash)
+    }
 
-        U128(price)
+    /// Hash of an order, the key it is registered under
+    pub fn hash_order(&self, order: Order) -> String {
+        order.hash_order()
     }
 
-    /// Get current price and order information for a Dutch auction order
-    /// not a public function
-    pub fn get_price_info(
+    /// Price at the current block time on `curve`, linear by default
+    pub fn calc_price(
         &self,
-        order: Order,
         start_time: u64,
         end_time: u64,
         start_price: U128,
         end_price: U128,
-    ) -> PriceInfo {
-        // Calculate current price
-        let current_price = self.calc_price(start_time, end_time, start_price, end_price);
+        curve: Option<AuctionCurve>,
+    ) -> U128 {
+        let current_time = env::block_timestamp() / 1_000_000; // in ms
 
-        // Generate order hash
-        let order_hash = &order.hash_order();
-        let order_hash2 = order_hash.clone();
+        // The linear curve takes any two points, as before curves existed
+        let curve = curve.unwrap_or_default();
+        curve.validate(start_time, end_time, start_price.0, end_price.0);
+
+        U128(curve.price_at(
+            start_time,
+            end_time,
+            start_price.0,
+            end_price.0,
+            current_time,
+        ))
+    }
+
+    /// Get current price and order information for a registered Dutch auction order
+    pub fn get_price_info(&self, order_hash: String) -> PriceInfo {
+        let auction = self.get_auction(&order_hash);
+        let (start_time, end_time) = (auction.start_time, auction.end_time);
+
+        // Calculate current price from the stored auction parameters
+        let current_price = U128(self.current_price(auction));
 
         // Calculate time progress percentage
         let current_time = env::block_timestamp() / 1_000_000; // in ms
@@ -116,58 +262,172 @@ impl DutchAuctionCalculator {
         let time_elapsed_percent = if end_time <= start_time {
             100 // Avoid division by zero
         } else {
-            let elapsed = current_time as u64 - start_time;
+            let elapsed = current_time.saturating_sub(start_time);
             let total = end_time - start_time;
-            let percent = (elapsed * 100) / total;
-            std::cmp::min(100, percent as u8) // Clamp to 0-100
+            let percent = (elapsed as u128 * 100) / total as u128;
+            std::cmp::min(100, percent) as u8 // Clamp to 0-100
         };
 
         // Check if auction is still active
-        let is_active = (current_time as u64) < end_time;
+        let is_active = current_time < end_time;
 
         PriceInfo {
             current_price,
-            order_hash: order_hash2,
+            order_hash,
             time_elapsed_percent,
             is_active,
         }
     }
 
     /// Fill an order at the current Dutch auction price
-    /// Returns the filled order information including the price and taker
+    /// `making_amount` is the part of the order to fill, None fills everything that remains
+    /// The taker fills for itself, so it must be the caller
+    /// Returns the filled order information including the price, taking amount and taker
     pub fn fill_order(
         &mut self,
-        order: Order,
+        order_hash: String,
         taker: AccountId,
-        start_time: u64,
-        end_time: u64,
-        start_price: U128,
-        end_price: U128,
+        making_amount: Option<U128>,
     ) -> FilledOrderInfo {
-        // Calculate current price based on the auction parameters
-        let current_price = self.calc_price(start_time, end_time, start_price, end_price);
-
-        // Generate order hash for tracking
-        let order_hash = &order.hash_order();
-        let order_hash2 = order_hash.clone();
+        require!(
+            taker == env::predecessor_account_id(),
+            "Only the taker can fill an order"
+        );
+        let auction = self.get_auction(&order_hash);
 
         // Get current timestamp
         let current_time = env::block_timestamp() / 1_000_000; // in ms
 
         // Ensure auction is still active
+        require!(current_time < auction.end_time, "Auction has already ended");
+
+        let remaining = auction.remaining_amount();
+        require!(remaining > 0, "Order already fully filled");
+        let fill_amount = making_amount.map_or(remaining, |amount| amount.0);
+        require!(fill_amount > 0, "Fill amount must be positive");
         require!(
-            (current_time as u64) < end_time,
-            "Auction has already ended"
+            fill_amount <= remaining,
+            "Fill amount exceeds remaining amount"
         );
-        // We could also handle the partial fills here itself. 
+
+        // Calculate current price and the taking amount for this part
+        let current_price = self.current_price(auction);
+        let taking_amount = Self::taking_amount(auction, current_price, fill_amount);
+
+        let auction = self.orders.get_mut(&order_hash).unwrap();
+        auction.filled_amount = U128(auction.filled_amount.0 + fill_amount);
+
         FilledOrderInfo {
-            order_hash: order_hash2,
-            maker: order.maker,
+            order_hash,
+            maker: auction.order.maker.clone(),
+            maker_public_key: auction.maker_public_key.clone(),
+            maker_signature: auction.signature.clone(),
             taker,
-            maker_asset: order.maker_asset,
-            making_amount: order.making_amount,
-            fill_price: current_price,
+            maker_asset: auction.order.maker_asset.clone(),
+            making_amount: U128(fill_amount),
+            taking_amount: U128(taking_amount),
+            remaining_amount: U128(auction.remaining_amount()),
+            fill_price: U128(current_price),
             fill_time: U128(current_time as u128),
         }
     }
+
+    /// Cumulative making amount filled for an order
+    pub fn get_filled_amount(&self, order_hash: String) -> U128 {
+        self.get_auction(&order_hash).filled_amount
+    }
+
+    /// Making amount still available to fill
+    pub fn get_remaining_amount(&self, order_hash: String) -> U128 {
+        U128(self.get_auction(&order_hash).remaining_amount())
+    }
+
+    /// Whether `requested_amount` of the making asset can still be filled
+    pub fn can_partial_fill(&self, order_hash: String, requested_amount: U128) -> bool {
+        requested_amount.0 <= self.get_auction(&order_hash).remaining_amount()
+    }
+
+    /// Making amount received for `taking_amount` at the current price, capped at the remaining amount
+    /// The price is for the whole order here, so the amount scales by the order's making amount
+    /// (inverse of get_taking_amount); the Tezos calculator prices what is left and scales by the
+    /// available amount instead
+    pub fn get_making_amount(&self, order_hash: String, taking_amount: U128) -> U128 {
+        let auction = self.get_auction(&order_hash);
+        let current_price = self.current_price(auction);
+        if current_price == 0 {
+            return U128(0);
+        }
+        let requested = mul_div(
+            taking_amount.0,
+            auction.order.making_amount.0,
+            current_price,
+            false,
+        );
+        U128(std::cmp::min(requested, auction.remaining_amount()))
+    }
+
+    /// Taking amount owed for `making_amount` at the current price
+    pub fn get_taking_amount(&self, order_hash: String, making_amount: U128) -> U128 {
+        let auction = self.get_auction(&order_hash);
+        require!(
+            making_amount.0 <= auction.remaining_amount(),
+            "Fill amount exceeds remaining amount"
+        );
+        U128(Self::taking_amount(
+            auction,
+            self.current_price(auction),
+            making_amount.0,
+        ))
+    }
+}
+
+impl DutchAuctionCalculator {
+    /// A key of the maker: registered with `add_maker_key`, or the maker's access key
+    /// that signed this transaction (other accounts' keys can't be read on-chain)
+    fn is_maker_access_key(&self, maker: &AccountId, public_key: &PublicKey) -> bool {
+        self.maker_keys
+            .contains(&(maker.clone(), public_key.clone()))
+            || (env::signer_account_id() == *maker && env::signer_account_pk() == *public_key)
+    }
+
+    /// Require the attached deposit to cover the storage used since `initial_storage`
+    /// and refund the excess to the caller
+    fn charge_storage(&self, initial_storage: u64) {
+        let storage_cost =
+            NEAR_PER_STORAGE.saturating_mul((env::storage_usage() - initial_storage) as u128);
+        let attached = env::attached_deposit();
+        require!(
+            attached >= storage_cost,
+            format!(
+                "Attach at least {} yoctoNEAR to cover storage",
+                storage_cost.as_yoctonear()
+            )
+        );
+        let refund = attached.saturating_sub(storage_cost);
+        if !refund.is_zero() {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+    }
+
+    fn get_auction(&self, order_hash: &str) -> &AuctionOrder {
+        self.orders
+            .get(order_hash)
+            .unwrap_or_else(|| env::panic_str("Order not registered"))
+    }
+
+    fn current_price(&self, auction: &AuctionOrder) -> u128 {
+        let current_time = env::block_timestamp() / 1_000_000; // in ms
+        auction.curve.price_at(
+            auction.start_time,
+            auction.end_time,
+            auction.start_price.0,
+            auction.end_price.0,
+            current_time,
+        )
+    }
+
+    /// The price covers the full order, a part pays pro rata, rounded up in the maker's favour
+    fn taking_amount(auction: &AuctionOrder, price: u128, making_amount: u128) -> u128 {
+        mul_div(price, making_amount, auction.order.making_amount.0, true)
+    }
 }
@@ -0,0 +1,83 @@
+// 256-bit intermediate math, amounts are yoctoNEAR-scale u128 so products overflow u128
+
+// Lint fires inside the uint macro expansion
+#[allow(clippy::manual_div_ceil)]
+mod u256 {
+    use uint::construct_uint;
+
+    construct_uint! {
+        pub struct U256(4);
+    }
+}
+
+pub use u256::U256;
+
+/// `a * b / denominator` without intermediate overflow, rounding up when `round_up` is set
+/// Panics if the denominator is zero or the result doesn't fit in a u128
+pub fn mul_div(a: u128, b: u128, denominator: u128, round_up: bool) -> u128 {
+    near_sdk::require!(denominator > 0, "Division by zero");
+    let product = U256::from(a) * U256::from(b);
+    let denominator = U256::from(denominator);
+    let mut result = product / denominator;
+    if round_up && !(product % denominator).is_zero() {
+        result += U256::one();
+    }
+    near_sdk::require!(result <= U256::from(u128::MAX), "Amount overflow");
+    result.as_u128()
+}
+
+/// 1.0 in 18-decimal fixed point
+pub const WAD: u128 = 1_000_000_000_000_000_000;
+
+/// `base ^ exp` for a fixed-point `base` no greater than 1.0, by repeated squaring
+pub fn pow_wad(base: u128, exp: u64) -> u128 {
+    near_sdk::require!(base <= WAD, "Base above 1.0");
+    let wad = U256::from(WAD);
+    let mut base = U256::from(base);
+    let mut result = wad;
+    let mut exp = exp;
+    while exp > 0 && !result.is_zero() {
+        if exp & 1 == 1 {
+            result = result * base / wad;
+        }
+        base = base * base / wad;
+        exp >>= 1;
+    }
+    // Never above 1.0
+    result.as_u128()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mul_div_uses_256_bit_intermediates() {
+        assert_eq!(mul_div(u128::MAX, 10, 10, false), u128::MAX);
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX, true), u128::MAX);
+    }
+
+    #[test]
+    fn mul_div_rounding() {
+        assert_eq!(mul_div(10, 1, 3, false), 3);
+        assert_eq!(mul_div(10, 1, 3, true), 4);
+        assert_eq!(mul_div(9, 1, 3, true), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount overflow")]
+    fn mul_div_rejects_results_above_u128() {
+        mul_div(u128::MAX, 2, 1, false);
+    }
+
+    #[test]
+    fn pow_wad_boundaries() {
+        let half = WAD / 2;
+        assert_eq!(pow_wad(half, 0), WAD);
+        assert_eq!(pow_wad(half, 1), half);
+        assert_eq!(pow_wad(half, 3), WAD / 8);
+        assert_eq!(pow_wad(WAD, u64::MAX), WAD);
+        assert_eq!(pow_wad(0, 1), 0);
+        assert_eq!(pow_wad(half, u64::MAX), 0);
+    }
+}
@@ -2,10 +2,12 @@ use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::U128;
 use near_sdk::require;
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, near, AccountId, PanicOnDefault};
+use near_sdk::{env, near, AccountId, PanicOnDefault, PublicKey};
+
+use crate::{AuctionCurve, OrderAuthorization};
 
 /// Order
-#[near(serializers = [json,Borsh])]
+#[near(serializers = [json, borsh])]
 pub struct Order {
     pub salt: u64,
     pub maker: AccountId,
@@ -13,6 +15,45 @@ pub struct Order {
     pub making_amount: U128,
 }
 
+/// Order signed by its maker, with the auction parameters stored on-chain
+#[near(serializers = [json, borsh])]
+pub struct AuctionOrder {
+    pub order: Order,
+    /// Key the maker signed the order with
+    pub maker_public_key: PublicKey,
+    /// Hex ed25519 signature of the `OrderAuthorization` hash
+    pub signature: String,
+    /// Auction start, in ms
+    pub start_time: u64,
+    /// Auction end, in ms
+    pub end_time: u64,
+    pub start_price: U128,
+    pub end_price: U128,
+    /// Shape of the price decay
+    pub curve: AuctionCurve,
+    /// Cumulative making amount filled so far
+    pub filled_amount: U128,
+}
+
+impl AuctionOrder {
+    /// What the maker signed to authorize this order on `auction_contract`
+    pub fn authorization<'a>(&'a self, auction_contract: &'a AccountId) -> OrderAuthorization<'a> {
+        OrderAuthorization {
+            auction_contract,
+            order: &self.order,
+            start_time: self.start_time,
+            end_time: self.end_time,
+            start_price: self.start_price,
+            end_price: self.end_price,
+            curve: &self.curve,
+        }
+    }
+
+    /// Making amount still available to fill
+    pub fn remaining_amount(&self) -> u128 {
+        self.order.making_amount.0 - self.filled_amount.0
+    }
+}
 
 impl Order {
     /// Generate a hash for an order
@@ -0,0 +1,45 @@
+// Maker signatures over auction orders
+// Anyone can register an order, the maker authorizes it and its auction parameters by signing
+use near_sdk::borsh::{self, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::{env, AccountId, CurveType, PublicKey};
+
+use crate::{AuctionCurve, Order};
+
+/// What the maker signs: the keccak256 hash of the borsh serialization of this struct, signed with ed25519
+#[derive(BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct OrderAuthorization<'a> {
+    pub auction_contract: &'a AccountId, // This contract, so the signature can't be used on another deployment
+    pub order: &'a Order,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub start_price: U128,
+    pub end_price: U128,
+    pub curve: &'a AuctionCurve,
+}
+
+impl OrderAuthorization<'_> {
+    /// Canonical hash the maker signs
+    pub fn hash(&self) -> [u8; 32] {
+        env::keccak256_array(&borsh::to_vec(self).unwrap())
+    }
+
+    /// Verify a 64-byte hex ed25519 signature of the hash against `public_key`
+    pub fn verify(&self, public_key: &PublicKey, signature: &str) -> bool {
+        if public_key.curve_type() != CurveType::ED25519 {
+            return false;
+        }
+        // PublicKey bytes start with the curve type
+        let Ok(public_key) = <[u8; 32]>::try_from(&public_key.as_bytes()[1..]) else {
+            return false;
+        };
+        let Some(signature) = hex::decode(signature.trim_start_matches("0x"))
+            .ok()
+            .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+        else {
+            return false;
+        };
+        env::ed25519_verify(&signature, &self.hash(), &public_key)
+    }
+}
@@ -1,4 +1,8 @@
+use ed25519_dalek::{Signer, SigningKey};
 use near_sdk::json_types::U128;
+use near_sdk::NearToken;
+use near_workspaces::types::{KeyType, SecretKey};
+use near_workspaces::Contract;
 use serde_json::json;
 
 #[tokio::test]
@@ -40,6 +44,42 @@ async fn test_dutch_auction_on(contract_wasm: &[u8]) -> Result<(), Box<dyn std::
     // We need to check what the current block timestamp is in the test environment
     // to determine what price we expect
 
+    // Piecewise curve that drops to 600 right after the start and stays there until 2100
+    let curve_price = contract
+        .view("calc_price")
+        .args_json(json!({
+            "start_time": 0u64,
+            "end_time": 4_102_444_800_000u64,
+            "start_price": U128::from(1000u128),
+            "end_price": U128::from(500u128),
+            "curve": {
+                "type": "piecewise",
+                "points": [
+                    { "delay": 1000u64, "price": U128::from(600u128) },
+                    { "delay": 4_000_000_000_000u64, "price": U128::from(600u128) }
+                ]
+            }
+        }))
+        .await?
+        .json::<U128>()?;
+    assert_eq!(curve_price, U128::from(600u128));
+
+    // Points must not raise the price
+    let rising_curve = contract
+        .view("calc_price")
+        .args_json(json!({
+            "start_time": 0u64,
+            "end_time": 4_102_444_800_000u64,
+            "start_price": U128::from(1000u128),
+            "end_price": U128::from(500u128),
+            "curve": {
+                "type": "piecewise",
+                "points": [{ "delay": 1000u64, "price": U128::from(1200u128) }]
+            }
+        }))
+        .await;
+    assert!(rising_curve.is_err());
+
     // Test order hash creation
     let order = json!({
         "salt": 12345,
@@ -58,16 +98,62 @@ async fn test_dutch_auction_on(contract_wasm: &[u8]) -> Result<(), Box<dyn std::
     let hash = hash_outcome.json::<String>()?;
     assert!(!hash.is_empty(), "Order hash should not be empty");
 
-    // Test price info
+    // Register the order, the contract account is the maker and signs with its access key
+    let mut register_args = json!({
+        "order": order,
+        "start_time": start_time,
+        "end_time": end_time,
+        "start_price": start_price,
+        "end_price": end_price
+    });
+    let maker_key = contract.as_account().secret_key();
+    register_args["maker_public_key"] = json!(maker_key.public_key());
+    register_args["signature"] = json!(sign_order(&contract, maker_key, &register_args).await?);
+
+    // A signature from another key is rejected
+    let mut forged_args = register_args.clone();
+    forged_args["signature"] = json!(
+        sign_order(
+            &contract,
+            &SecretKey::from_seed(KeyType::ED25519, "forger"),
+            &register_args
+        )
+        .await?
+    );
+    let forged_outcome = contract
+        .call("register_order")
+        .args_json(forged_args)
+        .deposit(NearToken::from_millinear(10))
+        .transact()
+        .await?;
+    assert!(forged_outcome.is_failure());
+
+    let register_outcome = contract
+        .call("register_order")
+        .args_json(&register_args)
+        .deposit(NearToken::from_millinear(10))
+        .transact()
+        .await?;
+    assert!(
+        register_outcome.is_success(),
+        "{:#?}",
+        register_outcome.into_result().unwrap_err()
+    );
+    assert_eq!(register_outcome.json::<String>()?, hash);
+
+    // Registering the same order twice fails
+    let duplicate_outcome = contract
+        .call("register_order")
+        .args_json(&register_args)
+        .deposit(NearToken::from_millinear(10))
+        .transact()
+        .await?;
+    assert!(duplicate_outcome.is_failure());
+
+    // Test price info, looked up by order hash
     let price_info_outcome = contract
         .view("get_price_info")
-        .args_json(json!({
-            "order": order,
-            "start_time": start_time,
-            "end_time": end_time,
-            "start_price": start_price,
-            "end_price": end_price
-        }))
+        .args_json(json!({ "order_hash": hash }))
         .await?;
 
     let price_info = price_info_outcome.json::<serde_json::Value>()?;
@@ -88,5 +174,152 @@ async fn test_dutch_auction_on(contract_wasm: &[u8]) -> Result<(), Box<dyn std::
         "Price info should include is_active flag"
     );
 
+    // Partial fills on a long running auction with a flat price of 500 for the whole order
+    let partial_order = json!({
+        "salt": 67890,
+        "maker": contract.id(),
+        "maker_asset": "NEAR",
+        "making_amount": U128::from(1000u128)
+    });
+    let mut register_args = json!({
+        "order": partial_order,
+        "start_time": 0u64,
+        "end_time": 4_102_444_800_000u64, // year 2100, in ms
+        "start_price": U128::from(500u128),
+        "end_price": U128::from(500u128)
+    });
+
+    // Signed with a key the maker registered ahead of time, not one of its access keys
+    let signing_key = SecretKey::from_seed(KeyType::ED25519, "maker");
+    register_args["maker_public_key"] = json!(signing_key.public_key());
+    register_args["signature"] = json!(sign_order(&contract, &signing_key, &register_args).await?);
+
+    let unregistered_outcome = contract
+        .call("register_order")
+        .args_json(&register_args)
+        .deposit(NearToken::from_millinear(10))
+        .transact()
+        .await?;
+    assert!(unregistered_outcome.is_failure());
+
+    let add_key_outcome = contract
+        .call("add_maker_key")
+        .args_json(json!({ "public_key": signing_key.public_key() }))
+        .deposit(NearToken::from_millinear(10))
+        .transact()
+        .await?;
+    assert!(
+        add_key_outcome.is_success(),
+        "{:#?}",
+        add_key_outcome.into_result().unwrap_err()
+    );
+
+    let register_outcome = contract
+        .call("register_order")
+        .args_json(&register_args)
+        .deposit(NearToken::from_millinear(10))
+        .transact()
+        .await?;
+    let partial_hash = register_outcome.json::<String>()?;
+
+    // Only the taker itself can fill
+    let other = sandbox.dev_create_account().await?;
+    let foreign_fill_outcome = other
+        .call(contract.id(), "fill_order")
+        .args_json(json!({
+            "order_hash": partial_hash,
+            "taker": contract.id(),
+            "making_amount": U128::from(400u128)
+        }))
+        .transact()
+        .await?;
+    assert!(foreign_fill_outcome.is_failure());
+
+    let fill_outcome = contract
+        .call("fill_order")
+        .args_json(json!({
+            "order_hash": partial_hash,
+            "taker": contract.id(),
+            "making_amount": U128::from(400u128)
+        }))
+        .transact()
+        .await?;
+    let filled = fill_outcome.json::<serde_json::Value>()?;
+    assert_eq!(filled["making_amount"], "400");
+    assert_eq!(filled["taking_amount"], "200");
+    assert_eq!(filled["remaining_amount"], "600");
+
+    let remaining = contract
+        .view("get_remaining_amount")
+        .args_json(json!({ "order_hash": partial_hash }))
+        .await?
+        .json::<U128>()?;
+    assert_eq!(remaining, U128::from(600u128));
+
+    let can_fill = contract
+        .view("can_partial_fill")
+        .args_json(json!({ "order_hash": partial_hash, "requested_amount": U128::from(700u128) }))
+        .await?
+        .json::<bool>()?;
+    assert!(!can_fill, "Can't fill more than the remaining amount");
+
+    // Overfills are rejected
+    let overfill_outcome = contract
+        .call("fill_order")
+        .args_json(json!({
+            "order_hash": partial_hash,
+            "taker": contract.id(),
+            "making_amount": U128::from(700u128)
+        }))
+        .transact()
+        .await?;
+    assert!(overfill_outcome.is_failure());
+
+    // Without an amount the rest of the order is filled
+    let fill_outcome = contract
+        .call("fill_order")
+        .args_json(json!({ "order_hash": partial_hash, "taker": contract.id() }))
+        .transact()
+        .await?;
+    let filled = fill_outcome.json::<serde_json::Value>()?;
+    assert_eq!(filled["making_amount"], "600");
+    assert_eq!(filled["taking_amount"], "300");
+    assert_eq!(filled["remaining_amount"], "0");
+
+    let filled_amount = contract
+        .view("get_filled_amount")
+        .args_json(json!({ "order_hash": partial_hash }))
+        .await?
+        .json::<U128>()?;
+    assert_eq!(filled_amount, U128::from(1000u128));
+
     Ok(())
 }
+
+/// Sign an order the way a maker does: ed25519 over the hash from `get_order_signing_hash`
+async fn sign_order(
+    contract: &Contract,
+    secret_key: &SecretKey,
+    args: &serde_json::Value,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let hash = contract
+        .view("get_order_signing_hash")
+        .args_json(json!({
+            "order": args["order"],
+            "start_time": args["start_time"],
+            "end_time": args["end_time"],
+            "start_price": args["start_price"],
+            "end_price": args["end_price"],
+            "curve": args.get("curve")
+        }))
+        .await?
+        .json::<String>()?;
+
+    // Secret keys display as `ed25519:<base58 seed and public key>`
+    let key_bytes =
+        bs58::decode(secret_key.to_string().trim_start_matches("ed25519:")).into_vec()?;
+    let signing_key = SigningKey::from_bytes(&key_bytes[..32].try_into()?);
+    Ok(hex::encode(
+        signing_key.sign(&hex::decode(hash)?).to_bytes(),
+    ))
+}
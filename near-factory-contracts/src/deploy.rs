@@ -1,20 +1,32 @@
 use near_sdk::serde::Serialize;
 use near_sdk::{env, log, near, AccountId, NearToken, Promise, PromiseError, PublicKey};
 
-use crate::{Contract, ContractExt, NEAR_PER_STORAGE, TGAS};
+use crate::{ChainAddress, Contract, ContractExt, TimelockPolicy, NEAR_PER_STORAGE, TGAS};
 
 #[derive(Serialize)]
 #[serde(crate = "near_sdk::serde")]
-struct EscrowInitArgs {
+struct EscrowInitArgs<'a> {
     order_hash: String,
     hashlock: String,
+    hash_algorithm: Option<String>, // "sha256" or "keccak256", None uses the escrow default
+    parts_count: Option<u32>,       // Merkle secrets for partial fills, None for a single secret
     maker: AccountId,
-    taker_evm_address: String,
-    amount: String,         // U128 as string
-    safety_deposit: String, // U128 as string
-    withdrawal_timelock: u64,
-    public_withdrawal_timelock: u64,
-    cancellation_timelock: u64,
+    maker_public_key: Option<PublicKey>, // Key the maker registered, signs withdrawal authorizations
+    taker_address: ChainAddress,         // EVM, Tezos or NEAR taker
+    token: Option<AccountId>,            // NEP-141 token, None for native NEAR
+    amount: String,                      // U128 as string
+    safety_deposit: String,              // U128 as string
+    withdrawal_timelock: Option<u64>,
+    public_withdrawal_timelock: Option<u64>,
+    cancellation_timelock: Option<u64>,
+    public_cancellation_timelock: Option<u64>,
+    packed_timelocks: Option<String>, // TimelocksLib uint256 as hex, instead of the absolute timelocks
+    timelock_mode: Option<String>,    // "timestamp" or "block_height", None uses timestamps
+    // Factory policy, enforced by the escrow at init
+    timelock_policy: &'a TimelockPolicy,
+    rescue_delay: Option<u64>, // None uses the escrow default
+    depositor: AccountId,      // Resolver paying for the escrow (allowed to cancel it privately)
+    authorized_resolvers: Option<Vec<AccountId>>, // None lets only the depositor withdraw privately
 }
 
 #[near]
@@ -25,13 +37,21 @@ impl Contract {
         name: String,
         order_hash: String,
         hashlock: String,
+        hash_algorithm: Option<String>,
+        parts_count: Option<u32>,
         maker: AccountId,
-        taker_evm_address: String,
+        taker_address: ChainAddress,
+        token: Option<AccountId>,
         amount: String,         // U128 as string
         safety_deposit: String, // U128 as string
-        withdrawal_timelock: u64,
-        public_withdrawal_timelock: u64,
-        cancellation_timelock: u64,
+        withdrawal_timelock: Option<u64>,
+        public_withdrawal_timelock: Option<u64>,
+        cancellation_timelock: Option<u64>,
+        public_cancellation_timelock: Option<u64>,
+        packed_timelocks: Option<String>,
+        timelock_mode: Option<String>,
+        rescue_delay: Option<u64>,
+        authorized_resolvers: Option<Vec<AccountId>>,
         public_key: Option<PublicKey>,
     ) -> Promise {
         // Assert the sub-account is valid
@@ -54,7 +74,12 @@ impl Contract {
         // Parse the escrow amounts
         let escrow_amount: u128 = amount.parse().unwrap();
         let safety_amount: u128 = safety_deposit.parse().unwrap();
-        let escrow_deposit = NearToken::from_yoctonear(escrow_amount + safety_amount);
+        // Token escrows only lock the safety deposit in NEAR, the amount is sent with ft_transfer_call
+        let escrow_deposit = if token.is_some() {
+            NearToken::from_yoctonear(safety_amount)
+        } else {
+            NearToken::from_yoctonear(escrow_amount + safety_amount)
+        };
 
         // Total needed = account creation + escrow deposit
         let total_needed = account_creation_cost.saturating_add(escrow_deposit);
@@ -66,13 +91,24 @@ impl Contract {
         let init_args = near_sdk::serde_json::to_vec(&EscrowInitArgs {
             order_hash,
             hashlock,
+            hash_algorithm,
+            parts_count,
+            maker_public_key: self.maker_keys.get(&maker).cloned(),
             maker,
-            taker_evm_address,
+            taker_address,
+            token,
             amount,
             safety_deposit,
             withdrawal_timelock,
             public_withdrawal_timelock,
             cancellation_timelock,
+            public_cancellation_timelock,
+            packed_timelocks,
+            timelock_mode,
+            timelock_policy: &self.timelock_policy,
+            rescue_delay,
+            depositor: env::predecessor_account_id(),
+            authorized_resolvers,
         })
         .unwrap();
 
@@ -1,8 +1,9 @@
 // Find all our documentation at https://docs.near.org
-use near_sdk::store::LazyOption;
-use near_sdk::{near, Gas, NearToken};
+use near_sdk::store::{LazyOption, LookupMap};
+use near_sdk::{near, AccountId, Gas, NearToken, PublicKey};
 
 mod deploy;
+mod maker;
 mod manager;
 
 const NEAR_PER_STORAGE: NearToken = NearToken::from_yoctonear(10u128.pow(19)); // 10e19yⓃ
@@ -10,6 +11,47 @@ const DEFAULT_CONTRACT: &[u8] = include_bytes!("../escrow/near_contracts.wasm");
 const TGAS: Gas = Gas::from_tgas(1);
 const NO_DEPOSIT: NearToken = NearToken::from_near(0); // 0yⓃ
 
+/// Taker address on the other leg of the swap (same JSON as the escrow's ChainAddress, validated there)
+#[near(serializers = [json])]
+#[serde(tag = "chain", content = "address", rename_all = "snake_case")]
+pub enum ChainAddress {
+    Evm(String),
+    Tezos(String),
+    Near(AccountId),
+}
+
+/// Allowed start of a timelock stage, in seconds (blocks in block height mode) after escrow creation
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Copy)]
+pub struct StageBounds {
+    pub min: u64,
+    pub max: u64,
+}
+
+/// Timelock bounds every escrow deployed by this factory must respect (same as the escrow's TimelockPolicy)
+#[near(serializers = [json, borsh])]
+#[derive(Clone)]
+pub struct TimelockPolicy {
+    pub withdrawal: StageBounds,
+    pub public_withdrawal: StageBounds,
+    pub cancellation: StageBounds,
+    pub public_cancellation: StageBounds,
+    pub min_stage_gap: u64,
+}
+
+impl Default for TimelockPolicy {
+    fn default() -> Self {
+        let within = |max: u64| StageBounds { min: 0, max };
+        Self {
+            withdrawal: within(86400),
+            public_withdrawal: within(86400 * 6),
+            cancellation: within(86400 * 6),
+            public_cancellation: within(86400 * 6),
+            min_stage_gap: 60,
+        }
+    }
+}
+
 // Define the contract structure
 #[near(contract_state)]
 pub struct Contract {
@@ -19,6 +61,10 @@ pub struct Contract {
     // Please note that it is much more efficient to **not** store this
     // code in the state, and directly use `DEFAULT_CONTRACT`
     // However, this does not enable to update the stored code.
+    // Passed to every escrow, which validates its timelocks against it
+    timelock_policy: TimelockPolicy,
+    // Key each maker registered to sign withdrawal authorizations on its escrows
+    maker_keys: LookupMap<AccountId, PublicKey>,
 }
 
 // Define the default, which automatically initializes the contract
@@ -26,6 +72,8 @@ impl Default for Contract {
     fn default() -> Self {
         Self {
             code: LazyOption::new("code".as_bytes(), Some(DEFAULT_CONTRACT.to_vec())),
+            timelock_policy: TimelockPolicy::default(),
+            maker_keys: LookupMap::new(b"m"),
         }
     }
 }
@@ -0,0 +1,55 @@
+use near_sdk::{env, near, require, AccountId, CurveType, Promise, PublicKey};
+
+use crate::{Contract, ContractExt, NEAR_PER_STORAGE};
+
+#[near]
+impl Contract {
+    /// Register the key the caller signs withdrawal authorizations with
+    /// Every escrow deployed for the caller as maker gets it, replacing a previous key
+    /// The attached deposit covers the storage, the excess is refunded
+    #[payable]
+    pub fn set_maker_public_key(&mut self, public_key: PublicKey) {
+        require!(
+            public_key.curve_type() == CurveType::ED25519,
+            "Only ed25519 keys are supported"
+        );
+        let initial_storage = env::storage_usage();
+        self.maker_keys
+            .insert(env::predecessor_account_id(), public_key);
+        self.maker_keys.flush();
+
+        let storage_cost = NEAR_PER_STORAGE
+            .saturating_mul(env::storage_usage().saturating_sub(initial_storage) as u128);
+        let attached = env::attached_deposit();
+        require!(
+            attached >= storage_cost,
+            format!(
+                "Attach at least {} yoctoNEAR to cover storage",
+                storage_cost.as_yoctonear()
+            )
+        );
+        let refund = attached.saturating_sub(storage_cost);
+        if !refund.is_zero() {
+            let _ = Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+    }
+
+    /// Remove the caller's key, escrows already deployed keep theirs
+    /// The released storage is refunded to the caller
+    pub fn remove_maker_public_key(&mut self) {
+        let initial_storage = env::storage_usage();
+        let removed = self.maker_keys.remove(&env::predecessor_account_id());
+        require!(removed.is_some(), "No maker public key registered");
+        self.maker_keys.flush();
+
+        let refund = NEAR_PER_STORAGE
+            .saturating_mul(initial_storage.saturating_sub(env::storage_usage()) as u128);
+        if !refund.is_zero() {
+            let _ = Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+    }
+
+    pub fn get_maker_public_key(&self, maker: AccountId) -> Option<&PublicKey> {
+        self.maker_keys.get(&maker)
+    }
+}
@@ -1,6 +1,13 @@
-use near_sdk::{env, near};
+use near_sdk::store::{LazyOption, LookupMap};
+use near_sdk::{env, near, require};
 
-use crate::{Contract, ContractExt};
+use crate::{Contract, ContractExt, StageBounds, TimelockPolicy};
+
+/// Factory state before the timelock policy and maker keys were added
+#[near(serializers = [borsh])]
+struct OldContract {
+    code: LazyOption<Vec<u8>>,
+}
 
 #[near]
 impl Contract {
@@ -16,4 +23,41 @@ impl Contract {
         // If a contract wants to update themselves, they can ask for the code needed
         self.code.get().as_ref().unwrap()
     }
+
+    #[private]
+    pub fn set_timelock_policy(&mut self, policy: TimelockPolicy) {
+        let stages = [
+            (&policy.withdrawal, "Withdrawal"),
+            (&policy.public_withdrawal, "Public withdrawal"),
+            (&policy.cancellation, "Cancellation"),
+            (&policy.public_cancellation, "Public cancellation"),
+        ];
+        for (StageBounds { min, max }, name) in stages {
+            require!(
+                min <= max,
+                format!("{name} bounds: min must not exceed max")
+            );
+        }
+
+        // Only applies to escrows deployed from now on
+        self.timelock_policy = policy;
+    }
+
+    /// Upgrade a factory deployed before the timelock policy and maker keys existed
+    /// Call it right after deploying the new code, the stored escrow code is kept
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let old: OldContract =
+            env::state_read().unwrap_or_else(|| env::panic_str("Factory state not found"));
+        Self {
+            code: old.code,
+            timelock_policy: TimelockPolicy::default(),
+            maker_keys: LookupMap::new(b"m"),
+        }
+    }
+
+    pub fn get_timelock_policy(&self) -> &TimelockPolicy {
+        &self.timelock_policy
+    }
 }
@@ -58,6 +58,10 @@ const nearAccount = new Account(NEAR_CONFIG.accountId, nearProvider, nearSigner)
 
 const tezos = new TezosToolkit(TEZOS_CONFIG.rpc);
 
+// NEAR escrows take 32-byte hex secrets; plain strings are zero padded like the EVM side does
+const toSecret32 = (secret) =>
+    ethers.isHexString(secret, 32) ? secret : ethers.zeroPadValue(ethers.toUtf8Bytes(secret), 32);
+
 
 
 app.post('/auction/start', async (req, res) => {
@@ -85,19 +89,54 @@ app.post('/auction/start', async (req, res) => {
     const endPrice = parseNearAmount('8');     // 8 NEAR
 
 
-    // Function to get current price info for an order
-    async function getPriceInfo() {
+    const auctionArgs = {
+        order,
+        start_time: startTime,
+        end_time: endTime,
+        start_price: startPrice,
+        end_price: endPrice
+    };
+
+    // 1. Register the order signed by the maker. The resolver account signs here, so the
+    // maker must be this account or have added the resolver key with `add_maker_key`
+    async function registerOrder() {
+        try {
+            const signingHash = await nearAccount.callFunction({
+                contractId: 'dutchauction22.testnet',
+                methodName: 'get_order_signing_hash',
+                args: auctionArgs
+            });
+
+            const { signature } = nearSigner.getKeyPair().sign(Buffer.from(signingHash, 'hex'));
+            const makerPublicKey = await nearSigner.getPublicKey();
+
+            const orderHash = await nearAccount.callFunction({
+                contractId: 'dutchauction22.testnet',
+                methodName: 'register_order',
+                args: {
+                    ...auctionArgs,
+                    maker_public_key: makerPublicKey.toString(),
+                    signature: Buffer.from(signature).toString('hex')
+                },
+                deposit: parseNearAmount('0.1'), // Order storage, the excess is refunded
+                gas: "30000000000000"
+            });
+
+            console.log("Order hash:", orderHash);
+            return orderHash;
+        } catch (error) {
+            console.error("Error registering order:", error);
+            throw error;
+        }
+    }
+
+    // Function to get current price info for a registered order
+    async function getPriceInfo(orderHash) {
         try {
             const priceInfo = await nearAccount.callFunction({
                 contractId: 'dutchauction22.testnet',
                 methodName: 'get_price_info',
-                args: {
-                    order,
-                    start_time: startTime,
-                    end_time: endTime,
-                    start_price: startPrice,
-                    end_price: endPrice
-                }
+                args: { order_hash: orderHash }
             });
 
             console.log("Price Info:", priceInfo);
@@ -113,15 +152,16 @@ app.post('/auction/start', async (req, res) => {
     }
 
 
+    const orderHash = await registerOrder();
+
     // 2. Get the current price information
-    const priceInfo = await getPriceInfo();
+    const priceInfo = await getPriceInfo(orderHash);
 
-    // 3. In a real implementation, you would save this order hash
-    // for verification when the order is filled from the other chain
+    // 3. The order hash is saved for `/auction/fillOrder` when the order is filled from the other chain
     console.log("Dutch auction is ready for cross-chain fulfillment");
 
-    fs.writeFileSync(path.join(__dirname, 'orderDetails.json'), JSON.stringify({ priceInfo, startTime, endTime, startPrice, endPrice, order }, null, 2));
-    res.json({ priceInfo, startTime, endTime, startPrice, endPrice, order })
+    fs.writeFileSync(path.join(__dirname, 'orderDetails.json'), JSON.stringify({ orderHash, priceInfo, startTime, endTime, startPrice, endPrice, order }, null, 2));
+    res.json({ orderHash, priceInfo, startTime, endTime, startPrice, endPrice, order })
 
 });
 
@@ -129,7 +169,7 @@ app.post('/auction/start', async (req, res) => {
 app.post('/auction/fillOrder', async (req, res) => {
 
 
-    const { taker, startTime, endTime, startPrice, endPrice, order } = req.body;
+    const { orderHash, makingAmount } = req.body;
 
 
     const orderpath = path.join(__dirname, 'orderDetails.json');
@@ -140,17 +180,14 @@ app.post('/auction/fillOrder', async (req, res) => {
     const now = Math.floor(Date.now());
 
 
-    // The resolver fills the order here
+    // The resolver fills the order here, the taker must be the calling account
     const filledOrderInfo = await nearAccount.callFunction({
         contractId: 'dutchauction22.testnet',
         methodName: 'fill_order',
         args: {
-            order: order || orderDetails.order,
-            taker,
-            start_time: startTime || orderDetails.startTime,
-            end_time: endTime || orderDetails.endTime,
-            start_price: startPrice || orderDetails.startPrice,
-            end_price: endPrice || orderDetails.endPrice
+            order_hash: orderHash || orderDetails.orderHash,
+            taker: nearAccount.accountId,
+            making_amount: makingAmount || null
         }
     });
 
@@ -295,30 +332,37 @@ app.post('/deploy/near', async (req, res) => {
             amount,
             safetyDeposit,
             withdrawalPeriod = 10,
+            publicWithdrawalPeriod = 3600,
             cancellationPeriod = 7201,
+            publicCancellationPeriod = 10801,
             uniqueName,
             secret
         } = req.body;
 
         // Use exact same function call as near.js
-        const hash = ethers.sha256(ethers.toUtf8Bytes(secret));
+        const secret32 = toSecret32(secret || '123');
+        const hash = ethers.sha256(secret32);
         const result = await nearAccount.callFunction({
             contractId: "escrowfac22.testnet",
             methodName: "create_factory_subaccount_and_deploy",
             args: {
                 name: uniqueName || "randomname",
                 order_hash: orderHash || "0x1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef",
-                hashlock: hash || "0xa665a45920422f9d417e4867efdc4fb8a04a1f3fff1fa07e998e86f7f7a27ae3",
+                hashlock: hash,
                 maker: maker || "othercap7803.testnet",
-                taker_evm_address: takerEvmAddress || "0x6F1859694601891B7ED021c3Fefd390AB776d5C0",
+                taker_address: {
+                    chain: "evm",
+                    address: ethers.getAddress((takerEvmAddress || "0x6F1859694601891B7ED021c3Fefd390AB776d5C0").toLowerCase()),
+                },
                 amount: amount || "100000000000000000000000",
                 safety_deposit: safetyDeposit || "100000000000000000000",
                 withdrawal_timelock: Math.floor(Date.now() / 1000) + withdrawalPeriod,
-                public_withdrawal_timelock: Math.floor(Date.now() / 1000) + 30,
+                public_withdrawal_timelock: Math.floor(Date.now() / 1000) + publicWithdrawalPeriod,
                 cancellation_timelock: Math.floor(Date.now() / 1000) + cancellationPeriod,
-                publickey: null,
+                public_cancellation_timelock: Math.floor(Date.now() / 1000) + publicCancellationPeriod,
+                public_key: null,
             },
-            deposit: "2800000000000000000000000",
+            deposit: "4500000000000000000000000", // Escrow account storage (~2.9 NEAR) + amount + safety deposit
             gas: "50000000000000",
         });
 
@@ -326,7 +370,7 @@ app.post('/deploy/near', async (req, res) => {
             success: true,
             network: 'NEAR',
             result,
-            secret: secret || '123', // From near.js withdraw function
+            secret: secret32, // From near.js withdraw function
             contractName: `${uniqueName || "randomname"}.escrowfac22.testnet`
         });
 
@@ -601,7 +645,7 @@ app.post('/withdraw/near', async (req, res) => {
             contractId: contractId,
             methodName: "withdraw",
             args: {
-                secret: secret || "123",
+                secret: toSecret32(secret || "123"),
             },
             gas: "30000000000000"
         });
@@ -611,7 +655,7 @@ app.post('/withdraw/near', async (req, res) => {
             network: 'NEAR',
             contractId,
             result,
-            secret: secret || "123"
+            secret: toSecret32(secret || "123")
         });
 
     } catch (error) {
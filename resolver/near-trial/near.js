@@ -21,17 +21,18 @@ async function factory() {
         args: {
             name : "uniquename234",
             order_hash: "0x1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef", // 32-byte hash (64 hex chars)
-            hashlock: "0xa665a45920422f9d417e4867efdc4fb8a04a1f3fff1fa07e998e86f7f7a27ae3", // 32-byte hash
+            hashlock: "0xf738c722a8535b85ca69b53ec949a611c6e09e6de0b093c72a9327f2c376c8a5", // sha256 of the 32-byte secret below
             maker: "othercap7803.testnet", // NEAR account ID
-            taker_evm_address: "0x6F1859694601891B7ED021c3Fefd390AB776d5C0", // EVM address as string
+            taker_address: { chain: "evm", address: "0x6f1859694601891b7ed021c3fefd390ab776d5c0" }, // Taker address on the other chain
             amount: "1000000000000000000000000", // Amount in yoctoNEAR (1 NEAR)
             safety_deposit: "1000000000000000000000", // Safety deposit in yoctoNEAR (0.1 NEAR)
             withdrawal_timelock: Math.floor(Date.now() / 1000) + 20, // 30 minutes from now (minimum required)
-            public_withdrawal_timelock: Math.floor(Date.now() / 1000)+ 30, // 1 hour from now
+            public_withdrawal_timelock: Math.floor(Date.now() / 1000) + 3600, // 1 hour from now (stages at least a minute apart)
             cancellation_timelock: Math.floor(Date.now() / 1000) + 7201,
-            publickey: null, // 2 hours from now
+            public_cancellation_timelock: Math.floor(Date.now() / 1000) + 10801, // 3 hours from now
+            public_key: null,
         },
-        deposit: "2800000000000000000000000", // Total deposit in yoctoNEAR (1.1 NEAR)
+        deposit: "4500000000000000000000000", // Escrow account storage (~2.9 NEAR) + amount + safety deposit
         gas: "50000000000000",
         
     }
@@ -73,7 +74,7 @@ async function main() {
         contractId: "escrow-unitedefi.testnet",
         methodName: "withdraw",
         args: {
-            secret: "123",
+            secret: "0x0000000000000000000000000000000000000000000000000000000000313233", // "123" zero padded to 32 bytes
 
         },
         gas: "30000000000000"